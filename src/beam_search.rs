@@ -0,0 +1,356 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::NGramModel;
+
+// Stupid backoff (Brants et al., 2007), the weight applied for each word dropped
+// from the context when a required word has to be forced into a sentence
+const BACKOFF_WEIGHT: f64 = 0.4;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BeamSearchConfig {
+    // Number of partial sentences kept at each step
+    pub beam_width: usize,
+    // Number of complete sentences returned
+    pub n_best: usize,
+    // Sentences still unfinished after this many words are dropped
+    pub max_sentence_size: usize,
+    // Scores are divided by length ^ length_normalisation, 0.0 disables it.
+    // Without it, beam search prefers very short sentences
+    pub length_normalisation: f64,
+    // Probabilities of words already in the sentence are divided by this, 1.0 disables it
+    pub repetition_penalty: f64
+}
+
+impl Default for BeamSearchConfig {
+    fn default() -> BeamSearchConfig {
+        BeamSearchConfig {
+            beam_width: 5,
+            n_best: 3,
+            max_sentence_size: 25,
+            length_normalisation: 0.0,
+            repetition_penalty: 1.0
+        }
+    }
+}
+
+impl BeamSearchConfig {
+    pub fn validate(
+        &self
+    ) -> Result<(), String> {
+        if self.beam_width == 0 {
+            return Err("beam_width must be at least 1".to_string());
+        }
+        if !(self.repetition_penalty >= 1.0 && self.repetition_penalty.is_finite()) {
+            return Err(format!("repetition_penalty must be at least 1.0, got: {:?}", self.repetition_penalty));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScoredSentence {
+    pub sentence: String,
+    // Sum of log2 P(word | history) over the sentence, including its </S>. When a history
+    // wasn't seen in training the word's probability given a shorter history is used
+    // unweighted (forced in words in generate_constrained are scaled by BACKOFF_WEIGHT
+    // instead), so for backed off sentences this isn't the model's true probability
+    pub log_probability: f64,
+    // The log probability after the repetition penalty and length normalisation
+    pub score: f64
+}
+
+// Words that must, and must not, appear in a generated sentence
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LexicalConstraints {
+    pub required_words: Vec<String>,
+    pub banned_words: Vec<String>
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConstraintError {
+    // A constraint isn't a single word once tokenised
+    InvalidConstraint(String),
+    // A required word never appears in the training text
+    UnknownWord(String),
+    // A word is both required and banned
+    ConflictingConstraint(String),
+    NoValidSentence { max_sentence_size: usize },
+    // The BeamSearchConfig is invalid
+    InvalidConfig(String)
+}
+
+impl fmt::Display for ConstraintError {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>
+    ) -> fmt::Result {
+        match self {
+            ConstraintError::InvalidConstraint(constraint) => {
+                write!(f, "Constraint must be a single word: {:?}", constraint)
+            }
+            ConstraintError::UnknownWord(word) => {
+                write!(f, "Required word never appears in the training text: {:?}", word)
+            }
+            ConstraintError::ConflictingConstraint(word) => {
+                write!(f, "Word is both required and banned: {:?}", word)
+            }
+            ConstraintError::NoValidSentence { max_sentence_size } => {
+                write!(f, "No sentence of at most {} words satisfies the constraints", max_sentence_size)
+            }
+            ConstraintError::InvalidConfig(message) => {
+                write!(f, "Invalid beam search config: {}", message)
+            }
+        }
+    }
+}
+
+impl Error for ConstraintError {}
+
+#[derive(Clone, Debug)]
+struct Beam {
+    words: Vec<String>,
+    history: Vec<String>,
+    log_probability: f64,
+    penalised_log_probability: f64,
+    // For constrained search, which of the required words the beam already contains
+    constraints_met: Vec<bool>
+}
+
+impl Beam {
+    fn constraints_met_count(
+        &self
+    ) -> usize {
+        self.constraints_met.iter().filter(|met| **met).count()
+    }
+
+    fn into_scored_sentence(
+        self,
+        length_normalisation: f64
+    ) -> ScoredSentence {
+        let score = self.score(length_normalisation);
+        ScoredSentence {
+            sentence: self.words[0..self.words.len() - 1].join(" "),
+            log_probability: self.log_probability,
+            score
+        }
+    }
+
+    fn score(
+        &self,
+        length_normalisation: f64
+    ) -> f64 {
+        self.penalised_log_probability / (self.words.len() as f64).powf(length_normalisation)
+    }
+}
+
+impl NGramModel {
+    pub fn beam_search(
+        &self,
+        config: &BeamSearchConfig
+    ) -> Result<Vec<ScoredSentence>, String> {
+        config.validate()?;
+
+        let mut beams: Vec<Beam> = vec![Beam {
+            words: vec![],
+            history: self.context_from_history(&[]),
+            log_probability: 0.0,
+            penalised_log_probability: 0.0,
+            constraints_met: vec![]
+        }];
+        let mut finished: Vec<Beam> = vec![];
+
+        for _step in 0..config.max_sentence_size + 1 {
+            let mut candidates: Vec<Beam> = vec![];
+            for beam in &beams {
+                for (word, probability) in self.backoff_continuations(&beam.history) {
+                    let candidate = self.extend_beam(beam, word, probability, config.repetition_penalty, &[]);
+                    if *candidate.words.last().unwrap() == self.end_of_sentence {
+                        // Lines that were only punctuation train an empty sentence, which isn't worth returning
+                        if candidate.words.len() > 1 {
+                            finished.push(candidate);
+                        }
+                    } else if candidate.words.len() < config.max_sentence_size + 1 {
+                        candidates.push(candidate);
+                    }
+                }
+            }
+
+            sort_beams(&mut candidates, config.length_normalisation);
+            candidates.truncate(config.beam_width);
+            beams = candidates;
+            if beams.is_empty() {
+                break;
+            }
+        }
+
+        sort_beams(&mut finished, config.length_normalisation);
+        Ok(finished
+            .into_iter()
+            .take(config.n_best)
+            .map(|beam| beam.into_scored_sentence(config.length_normalisation))
+            .collect())
+    }
+
+    // Grid beam search (Hokamp & Liu, 2017). Beams are grouped by how many required words
+    // they contain, so hypotheses that have met more constraints don't compete with
+    // (and get pruned by) more probable ones that have met fewer. Required words that
+    // can't follow the current history are forced in using a backed off probability.
+    // Returns the best complete sentence containing every required word and no banned word
+    pub fn generate_constrained(
+        &self,
+        constraints: &LexicalConstraints,
+        config: &BeamSearchConfig
+    ) -> Result<ScoredSentence, ConstraintError> {
+        config.validate().map_err(ConstraintError::InvalidConfig)?;
+
+        let required_words = self.tokenise_constraints(&constraints.required_words)?;
+        let banned_words = self.tokenise_constraints(&constraints.banned_words)?;
+        for word in &required_words {
+            if banned_words.contains(word) {
+                return Err(ConstraintError::ConflictingConstraint(word.to_string()));
+            }
+            if self.backoff_word_probability(&[], word) == 0.0 {
+                return Err(ConstraintError::UnknownWord(word.to_string()));
+            }
+        }
+
+        // grid[c] holds the beams containing c of the required words
+        let mut grid: Vec<Vec<Beam>> = vec![vec![]; required_words.len() + 1];
+        grid[0].push(Beam {
+            words: vec![],
+            history: self.context_from_history(&[]),
+            log_probability: 0.0,
+            penalised_log_probability: 0.0,
+            constraints_met: vec![false; required_words.len()]
+        });
+        let mut finished: Vec<Beam> = vec![];
+
+        for _step in 0..config.max_sentence_size + 1 {
+            let mut next_grid: Vec<Vec<Beam>> = vec![vec![]; required_words.len() + 1];
+            for beam in grid.iter().flatten() {
+                let continuations = self.backoff_continuations(&beam.history);
+
+                // Generate freely from the model
+                for (word, probability) in &continuations {
+                    if banned_words.contains(word) {
+                        continue;
+                    }
+                    if *word == self.end_of_sentence {
+                        // Only finish once every required word is in, and not with an empty sentence
+                        if beam.constraints_met.iter().all(|met| *met) && !beam.words.is_empty() {
+                            finished.push(self.extend_beam(beam, word.to_string(), *probability, config.repetition_penalty, &required_words));
+                        }
+                        continue;
+                    }
+                    if beam.words.len() < config.max_sentence_size {
+                        let candidate = self.extend_beam(beam, word.to_string(), *probability, config.repetition_penalty, &required_words);
+                        next_grid[candidate.constraints_met_count()].push(candidate);
+                    }
+                }
+
+                // Force in required words the model wouldn't generate here
+                if beam.words.len() < config.max_sentence_size {
+                    for (i, word) in required_words.iter().enumerate() {
+                        if beam.constraints_met[i] || continuations.iter().any(|(w, _p)| w == word) {
+                            continue;
+                        }
+                        let probability = self.backoff_word_probability(&beam.history, word);
+                        let candidate = self.extend_beam(beam, word.to_string(), probability, config.repetition_penalty, &required_words);
+                        next_grid[candidate.constraints_met_count()].push(candidate);
+                    }
+                }
+            }
+
+            for cell in next_grid.iter_mut() {
+                sort_beams(cell, config.length_normalisation);
+                cell.truncate(config.beam_width);
+            }
+            grid = next_grid;
+            if grid.iter().all(|cell| cell.is_empty()) {
+                break;
+            }
+        }
+
+        sort_beams(&mut finished, config.length_normalisation);
+        match finished.into_iter().next() {
+            Some(beam) => Ok(beam.into_scored_sentence(config.length_normalisation)),
+            None => Err(ConstraintError::NoValidSentence { max_sentence_size: config.max_sentence_size })
+        }
+    }
+
+    fn tokenise_constraints(
+        &self,
+        constraints: &[String]
+    ) -> Result<Vec<String>, ConstraintError> {
+        let mut words: Vec<String> = vec![];
+        for constraint in constraints {
+            // Tokenised like the training text, so filters and punctuation cleaning apply
+            let mut tokens = self.string_to_string_vec(constraint.to_string());
+            if tokens.len() != 1 {
+                return Err(ConstraintError::InvalidConstraint(constraint.to_string()));
+            }
+            let word = tokens.pop().unwrap();
+            if !words.contains(&word) {
+                words.push(word);
+            }
+        }
+        Ok(words)
+    }
+
+    // P(word | history), backing off to shorter histories with a weight for each word dropped
+    fn backoff_word_probability(
+        &self,
+        history: &[String],
+        word: &String
+    ) -> f64 {
+        for context_start in 0..=history.len() {
+            let continuations = self.continuations(&history[context_start..]);
+            if let Some((_word, probability)) = continuations.iter().find(|(w, _p)| w == word) {
+                return probability * BACKOFF_WEIGHT.powi((history.len() - context_start) as i32);
+            }
+        }
+        0.0
+    }
+
+    fn extend_beam(
+        &self,
+        beam: &Beam,
+        word: String,
+        probability: f64,
+        repetition_penalty: f64,
+        required_words: &[String]
+    ) -> Beam {
+        let mut penalised_probability = probability;
+        if beam.words.contains(&word) {
+            penalised_probability /= repetition_penalty;
+        }
+
+        let mut candidate = beam.clone();
+        candidate.log_probability += probability.log2();
+        candidate.penalised_log_probability += penalised_probability.log2();
+        if self.degree > 1 {
+            candidate.history.remove(0);
+            candidate.history.push(word.to_string());
+        }
+        for (i, required_word) in required_words.iter().enumerate() {
+            if *required_word == word {
+                candidate.constraints_met[i] = true;
+            }
+        }
+        candidate.words.push(word);
+        candidate
+    }
+}
+
+// Best first, ties broken alphabetically so the search is deterministic
+fn sort_beams(
+    beams: &mut [Beam],
+    length_normalisation: f64
+) {
+    beams.sort_by(|a, b| {
+        b.score(length_normalisation)
+            .total_cmp(&a.score(length_normalisation))
+            .then_with(|| a.words.cmp(&b.words))
+    });
+}
@@ -0,0 +1,147 @@
+use rand::Rng;
+
+use crate::DecodingStrategy;
+use crate::GenerationConfig;
+use crate::NGramModel;
+use crate::TokenFilter;
+
+// Each direction stops after this many words if it hasn't reached the sentence boundary
+const MAX_WORDS_PER_DIRECTION: usize = 25;
+
+// A forward model and a reversed one trained on the same text, for generating
+// sentences that end with a given phrase or grow outward from a word in the middle
+pub struct BidirectionalModel {
+    pub forward: NGramModel,
+    pub backward: NGramModel
+}
+
+impl BidirectionalModel {
+    pub fn new(
+        degree: i64
+    ) -> BidirectionalModel {
+        BidirectionalModel {
+            forward: NGramModel::new(degree),
+            backward: NGramModel::new_reversed(degree)
+        }
+    }
+
+    pub fn add_token_filter(
+        &mut self,
+        filter: TokenFilter
+    ) {
+        self.forward.add_token_filter(filter.clone());
+        self.backward.add_token_filter(filter);
+    }
+
+    pub fn update_ngram_model(
+        &mut self,
+        line_of_text: String
+    ) {
+        self.forward.update_ngram_model(line_of_text.to_string());
+        self.backward.update_ngram_model(line_of_text);
+    }
+
+    pub fn update_ngram_model_document(
+        &mut self,
+        paragraphs: &[String]
+    ) {
+        self.forward.update_ngram_model_document(paragraphs);
+        self.backward.update_ngram_model_document(paragraphs);
+    }
+
+    pub fn generate_ending_with(
+        &self,
+        phrase: &str,
+        strategy: DecodingStrategy
+    ) -> String {
+        self.generate_ending_with_rng(phrase, strategy, &mut rand::thread_rng())
+    }
+
+    // A sentence ending with the phrase, e.g. "... God bless America", generated right to left
+    // by the backward model until it reaches the start of the sentence
+    pub fn generate_ending_with_rng<R: Rng>(
+        &self,
+        phrase: &str,
+        strategy: DecodingStrategy,
+        rng: &mut R
+    ) -> String {
+        NGramModel::validate_generation_arguments(&strategy, &GenerationConfig::default());
+
+        // Reversed during tokenisation, so the last word of the phrase comes first and
+        // the start padding of the backward model marks the end of the sentence
+        let mut words = self.backward.string_to_string_vec(phrase.to_string());
+        let history = self.backward.context_from_history(&words);
+        let config = GenerationConfig {
+            max_tokens: MAX_WORDS_PER_DIRECTION,
+            ..GenerationConfig::default()
+        };
+        words.extend(self.backward.generate_words_from_history(history, &strategy, &config, rng));
+        words.reverse();
+        words.join(" ")
+    }
+
+    pub fn generate_around(
+        &self,
+        seed: &str,
+        strategy: DecodingStrategy
+    ) -> String {
+        self.generate_around_with_rng(seed, strategy, &mut rand::thread_rng())
+    }
+
+    // A sentence containing the seed word (or words), grown outward in both directions.
+    // The backward model generates the words before the seed until it reaches <S>, then
+    // the forward model continues from the start of the sentence until it reaches </S>
+    pub fn generate_around_with_rng<R: Rng>(
+        &self,
+        seed: &str,
+        strategy: DecodingStrategy,
+        rng: &mut R
+    ) -> String {
+        NGramModel::validate_generation_arguments(&strategy, &GenerationConfig::default());
+        let config = GenerationConfig {
+            max_tokens: MAX_WORDS_PER_DIRECTION,
+            ..GenerationConfig::default()
+        };
+
+        // Nothing is known about what comes before the seed, so unlike a prompt its
+        // history isn't padded with start tokens
+        let mut reversed_words = self.backward.string_to_string_vec(seed.to_string());
+        if reversed_words.is_empty() {
+            // Without a seed there is nothing to grow around, and unpadded backward
+            // generation would start mid-sentence, so generate an ordinary sentence
+            return self.forward.generate_text_individual_sentence(&strategy, &config, rng);
+        }
+        self.backward.extend_unpadded(&mut reversed_words, &strategy, rng);
+
+        // Now the history runs from the start of the sentence, so padding is right again
+        let mut words: Vec<String> = reversed_words.into_iter().rev().collect();
+        let history = self.forward.context_from_history(&words);
+        words.extend(self.forward.generate_words_from_history(history, &strategy, &config, rng));
+        words.join(" ")
+    }
+}
+
+impl NGramModel {
+    // Generate words onto the end of words until the end of sentence token, using as much
+    // of the tail of words as history as there is, without padding it to (degree - 1)
+    fn extend_unpadded<R: Rng>(
+        &self,
+        words: &mut Vec<String>,
+        strategy: &DecodingStrategy,
+        rng: &mut R
+    ) {
+        let context_size: usize = (self.degree - 1).try_into().unwrap();
+        let start_size = words.len();
+        while words.len() - start_size < MAX_WORDS_PER_DIRECTION {
+            let history = &words[words.len().saturating_sub(context_size)..];
+            let generated_gram = match strategy.select_word(&self.backoff_continuations(history), rng) {
+                Some(word) => word,
+                None => { break; }
+            };
+            if generated_gram == self.end_of_sentence {
+                break;
+            }
+            words.push(generated_gram);
+        }
+    }
+}
@@ -0,0 +1,295 @@
+use crate::NGramModel;
+use crate::SotuDocument;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Party {
+    Unaffiliated,
+    Federalist,
+    DemocraticRepublican,
+    Democratic,
+    Whig,
+    NationalUnion,
+    Republican
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DeliveryType {
+    // Given in person, usually an "Address" before a joint session of Congress
+    Spoken,
+    // Sent to Congress as a written "Message", as every president did from Jefferson until Wilson
+    Written
+}
+
+// The party of every president, by the name the UCSB pages give them. Each president
+// has the party they were first elected for, or took office with, so John Quincy Adams
+// is a Democratic-Republican although he later ran as a National Republican, and
+// John Tyler a Whig although the party expelled him
+const PRESIDENT_PARTIES: &[(&str, Party)] = &[
+    ("George Washington", Party::Unaffiliated),
+    ("John Adams", Party::Federalist),
+    ("Thomas Jefferson", Party::DemocraticRepublican),
+    ("James Madison", Party::DemocraticRepublican),
+    ("James Monroe", Party::DemocraticRepublican),
+    ("John Quincy Adams", Party::DemocraticRepublican),
+    ("Andrew Jackson", Party::Democratic),
+    ("Martin van Buren", Party::Democratic),
+    ("William Henry Harrison", Party::Whig),
+    ("John Tyler", Party::Whig),
+    ("James K. Polk", Party::Democratic),
+    ("Zachary Taylor", Party::Whig),
+    ("Millard Fillmore", Party::Whig),
+    ("Franklin Pierce", Party::Democratic),
+    ("James Buchanan", Party::Democratic),
+    ("Abraham Lincoln", Party::Republican),
+    ("Andrew Johnson", Party::NationalUnion),
+    ("Ulysses S. Grant", Party::Republican),
+    ("Rutherford B. Hayes", Party::Republican),
+    ("James A. Garfield", Party::Republican),
+    ("Chester A. Arthur", Party::Republican),
+    ("Grover Cleveland", Party::Democratic),
+    ("Benjamin Harrison", Party::Republican),
+    ("William McKinley", Party::Republican),
+    ("Theodore Roosevelt", Party::Republican),
+    ("William Howard Taft", Party::Republican),
+    ("Woodrow Wilson", Party::Democratic),
+    ("Warren G. Harding", Party::Republican),
+    ("Calvin Coolidge", Party::Republican),
+    ("Herbert Hoover", Party::Republican),
+    ("Franklin D. Roosevelt", Party::Democratic),
+    ("Harry S. Truman", Party::Democratic),
+    ("Dwight D. Eisenhower", Party::Republican),
+    ("John F. Kennedy", Party::Democratic),
+    ("Lyndon B. Johnson", Party::Democratic),
+    ("Richard Nixon", Party::Republican),
+    ("Gerald R. Ford", Party::Republican),
+    ("Jimmy Carter", Party::Democratic),
+    ("Ronald Reagan", Party::Republican),
+    ("George Bush", Party::Republican),
+    ("William J. Clinton", Party::Democratic),
+    ("George W. Bush", Party::Republican),
+    ("Barack Obama", Party::Democratic),
+    ("Donald J. Trump", Party::Republican),
+    ("Joseph R. Biden, Jr.", Party::Democratic)
+];
+
+// Years whose State of the Union was given in person, (first, last) inclusive, for
+// documents without the site's categories. Titles don't tell, Kennedy's and Johnson's
+// speeches are "Annual Message"s like Hoover's written ones. Some of these years also
+// had a written message, e.g. Carter's in 1978, which only the categories tell apart
+const SPOKEN_YEARS: &[(i32, i32)] = &[
+    (1790, 1800), (1913, 1918), (1921, 1923), (1934, 1943), (1947, 1955),
+    (1957, 1972), (1974, 1980), (1982, i32::MAX)
+];
+
+// Lowercase words without punctuation, so "Joseph R. Biden, Jr." matches "joseph r biden jr"
+fn normalise_name(
+    name: &str
+) -> String {
+    name
+        .split_whitespace()
+        .map(|word| word.chars().filter(|c| c.is_alphanumeric()).collect::<String>().to_lowercase())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+pub fn party_of(
+    president: &str
+) -> Option<Party> {
+    let name = normalise_name(president);
+    PRESIDENT_PARTIES
+        .iter()
+        .find(|(president, _party)| normalise_name(president) == name)
+        .map(|(_president, party)| *party)
+}
+
+impl SotuDocument {
+    pub fn party(
+        &self
+    ) -> Option<Party> {
+        party_of(&self.president)
+    }
+
+    // By the site's categories where the document has them, "Written Messages" or
+    // "Spoken Addresses and Remarks". Without them State of the Union documents go by the
+    // years speeches were given, and anything else, or a document without a year, by its
+    // title: "... Address ..." is taken to be spoken
+    pub fn delivery_type(
+        &self
+    ) -> DeliveryType {
+        let categories: Vec<String> = self.categories.iter().map(|category| category.to_lowercase()).collect();
+        if categories.iter().any(|category| category.contains("written")) {
+            return DeliveryType::Written;
+        }
+        if categories.iter().any(|category| category.contains("spoken")) {
+            return DeliveryType::Spoken;
+        }
+        let is_state_of_the_union = self.collection.is_empty() || self.collection == "state-of-the-union";
+        if let (Some(year), true) = (self.year, is_state_of_the_union) {
+            if SPOKEN_YEARS.iter().any(|(first, last)| *first <= year && year <= *last) {
+                return DeliveryType::Spoken;
+            }
+            return DeliveryType::Written;
+        }
+        if self.title.to_lowercase().contains("address") {
+            return DeliveryType::Spoken;
+        }
+        DeliveryType::Written
+    }
+}
+
+// Filters for documents, the default accepts every document
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CorpusFilter {
+    // Matches any president whose name contains these words, e.g. "Lincoln" or "Roosevelt"
+    pub president: Option<String>,
+    pub party: Option<Party>,
+    // Inclusive, documents without a year are rejected when this is set
+    pub years: Option<(i32, i32)>,
+    pub delivery_type: Option<DeliveryType>,
+    // The name of a scraper collection, e.g. "inaugural"
+    pub collection: Option<String>
+}
+
+impl CorpusFilter {
+    pub fn new() -> CorpusFilter {
+        CorpusFilter::default()
+    }
+
+    pub fn with_president(
+        mut self,
+        president: &str
+    ) -> CorpusFilter {
+        self.president = Some(president.to_string());
+        self
+    }
+
+    pub fn with_party(
+        mut self,
+        party: Party
+    ) -> CorpusFilter {
+        self.party = Some(party);
+        self
+    }
+
+    pub fn with_years(
+        mut self,
+        first_year: i32,
+        last_year: i32
+    ) -> CorpusFilter {
+        self.years = Some((first_year, last_year));
+        self
+    }
+
+    pub fn with_delivery_type(
+        mut self,
+        delivery_type: DeliveryType
+    ) -> CorpusFilter {
+        self.delivery_type = Some(delivery_type);
+        self
+    }
+
+    pub fn with_collection(
+        mut self,
+        collection: &str
+    ) -> CorpusFilter {
+        self.collection = Some(collection.to_string());
+        self
+    }
+
+    pub fn accepts(
+        &self,
+        document: &SotuDocument
+    ) -> bool {
+        if let Some(president) = &self.president {
+            let wanted = format!(" {} ", normalise_name(president));
+            if !format!(" {} ", normalise_name(&document.president)).contains(&wanted) {
+                return false;
+            }
+        }
+        if self.party.is_some() && document.party() != self.party {
+            return false;
+        }
+        if let Some((first_year, last_year)) = self.years {
+            if !document.year.is_some_and(|year| first_year <= year && year <= last_year) {
+                return false;
+            }
+        }
+        if self.delivery_type.is_some_and(|delivery_type| document.delivery_type() != delivery_type) {
+            return false;
+        }
+        if self.collection.as_ref().is_some_and(|collection| document.collection != *collection) {
+            return false;
+        }
+        true
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Corpus {
+    documents: Vec<SotuDocument>
+}
+
+impl Corpus {
+    pub fn new(
+        documents: Vec<SotuDocument>
+    ) -> Corpus {
+        Corpus { documents }
+    }
+
+    pub fn documents(
+        &self
+    ) -> &[SotuDocument] {
+        &self.documents
+    }
+
+    pub fn len(
+        &self
+    ) -> usize {
+        self.documents.len()
+    }
+
+    pub fn is_empty(
+        &self
+    ) -> bool {
+        self.documents.is_empty()
+    }
+
+    // The documents the filter accepts, e.g.
+    //  corpus.filter(&CorpusFilter::new().with_party(Party::Democratic).with_years(1945, 1990))
+    pub fn filter(
+        &self,
+        filter: &CorpusFilter
+    ) -> Corpus {
+        let documents = self.documents
+            .iter()
+            .filter(|document| filter.accepts(document))
+            .cloned()
+            .collect();
+        Corpus { documents }
+    }
+
+    // Train on every line of every document, like update_ngram_model
+    pub fn train(
+        &self,
+        model: &mut NGramModel
+    ) {
+        for document in &self.documents {
+            for line in document.lines() {
+                if !line.is_empty() {
+                    model.update_ngram_model(line);
+                }
+            }
+        }
+    }
+
+    // Train on each document as a whole, keeping context across sentences, see update_ngram_model_document
+    pub fn train_documents(
+        &self,
+        model: &mut NGramModel
+    ) {
+        for document in &self.documents {
+            model.update_ngram_model_document(&document.paragraphs);
+        }
+    }
+}
@@ -0,0 +1,199 @@
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::Corpus;
+use crate::CorpusError;
+use crate::CorpusSource;
+use crate::FileError;
+use crate::SotuDocument;
+
+// The file listing the documents of a text archive, and their metadata
+const ARCHIVE_INDEX: &str = "index.jsonl";
+
+// A line of a text archive's index, a document without its paragraphs
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct ArchiveEntry {
+    file: String,
+    president: String,
+    date: String,
+    year: Option<i32>,
+    title: String,
+    url: String,
+    #[serde(default)]
+    collection: String,
+    #[serde(default)]
+    categories: Vec<String>
+}
+
+impl Corpus {
+    // JSON Lines, one document with its metadata per line:
+    //  {"president":"Joseph R. Biden, Jr.","date":"March 01, 2022","year":2022,"title":"...","url":"...","collection":"state-of-the-union","paragraphs":["..."]}
+    pub fn write_jsonl(
+        &self,
+        path: &Path
+    ) -> Result<(), CorpusError> {
+        let file = File::create(path).map_err(|e| FileError::new(path, &e))?;
+        let mut writer = BufWriter::new(file);
+        for document in self.documents() {
+            write_json_line(&mut writer, document).map_err(|e| FileError::new(path, &e))?;
+        }
+        writer.flush().map_err(|e| FileError::new(path, &e).into())
+    }
+
+    // A directory with a text file per document, paragraphs separated by blank lines like
+    // text_samples/biden_sotu_2022.txt, and an index.jsonl holding the metadata.
+    // TextArchiveSource reads the documents back exactly, as long as no paragraph holds a
+    // blank line, which TextExtractor never produces. TextDirectorySource can read the files
+    // too, but takes each line break inside a paragraph as the start of a new one
+    pub fn write_text_archive(
+        &self,
+        directory: &Path
+    ) -> Result<(), CorpusError> {
+        fs::create_dir_all(directory).map_err(|e| FileError::new(directory, &e))?;
+
+        let index_path = directory.join(ARCHIVE_INDEX);
+        let index_file = File::create(&index_path).map_err(|e| FileError::new(&index_path, &e))?;
+        let mut index = BufWriter::new(index_file);
+        for (position, document) in self.documents().iter().enumerate() {
+            let file = archive_file_name(position, document);
+            let text: String = document.paragraphs
+                .iter()
+                .map(|paragraph| format!("{}\n\n", paragraph))
+                .collect();
+            let text_path = directory.join(&file);
+            fs::write(&text_path, text).map_err(|e| FileError::new(&text_path, &e))?;
+
+            let entry = ArchiveEntry {
+                file,
+                president: document.president.to_string(),
+                date: document.date.to_string(),
+                year: document.year,
+                title: document.title.to_string(),
+                url: document.url.to_string(),
+                collection: document.collection.to_string(),
+                categories: document.categories.to_vec()
+            };
+            write_json_line(&mut index, &entry).map_err(|e| FileError::new(&index_path, &e))?;
+        }
+        index.flush().map_err(|e| FileError::new(&index_path, &e).into())
+    }
+}
+
+// Reads documents written by Corpus::write_jsonl
+#[derive(Clone, Debug, PartialEq)]
+pub struct JsonlSource {
+    pub path: PathBuf
+}
+
+impl JsonlSource {
+    pub fn new(
+        path: impl Into<PathBuf>
+    ) -> JsonlSource {
+        JsonlSource { path: path.into() }
+    }
+}
+
+impl CorpusSource for JsonlSource {
+    fn load(
+        &self
+    ) -> Result<Corpus, CorpusError> {
+        Ok(Corpus::new(read_json_lines(&self.path)?))
+    }
+}
+
+// Reads a directory written by Corpus::write_text_archive
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextArchiveSource {
+    pub directory: PathBuf
+}
+
+impl TextArchiveSource {
+    pub fn new(
+        directory: impl Into<PathBuf>
+    ) -> TextArchiveSource {
+        TextArchiveSource { directory: directory.into() }
+    }
+}
+
+impl CorpusSource for TextArchiveSource {
+    fn load(
+        &self
+    ) -> Result<Corpus, CorpusError> {
+        let entries: Vec<ArchiveEntry> = read_json_lines(&self.directory.join(ARCHIVE_INDEX))?;
+
+        let mut documents: Vec<SotuDocument> = vec![];
+        for entry in entries {
+            let text_path = self.directory.join(&entry.file);
+            let text = fs::read_to_string(&text_path).map_err(|e| FileError::new(&text_path, &e))?;
+            documents.push(SotuDocument {
+                president: entry.president,
+                date: entry.date,
+                year: entry.year,
+                title: entry.title,
+                url: entry.url,
+                collection: entry.collection,
+                categories: entry.categories,
+                paragraphs: archive_paragraphs(&text)
+            });
+        }
+        Ok(Corpus::new(documents))
+    }
+}
+
+// Paragraphs end with a blank line. Archives written before line breaks inside
+// paragraphs were kept have none, and a paragraph per line
+fn archive_paragraphs(
+    text: &str
+) -> Vec<String> {
+    if !text.contains("\n\n") {
+        return text.lines().filter(|line| !line.is_empty()).map(str::to_string).collect();
+    }
+    text.split("\n\n").filter(|paragraph| !paragraph.is_empty()).map(str::to_string).collect()
+}
+
+// e.g. 0007_2022_joseph-r-biden-jr.txt, numbered so the archive keeps the corpus order
+fn archive_file_name(
+    position: usize,
+    document: &SotuDocument
+) -> String {
+    let slug = document.president
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+        .collect::<Vec<_>>()
+        .join("-");
+    let year = document.year.map_or("unknown".to_string(), |year| year.to_string());
+    if slug.is_empty() {
+        return format!("{:04}_{}.txt", position + 1, year);
+    }
+    format!("{:04}_{}_{}.txt", position + 1, year, slug)
+}
+
+fn write_json_line<W: Write, T: Serialize>(
+    writer: &mut W,
+    value: &T
+) -> io::Result<()> {
+    serde_json::to_writer(&mut *writer, value)?;
+    writer.write_all(b"\n")
+}
+
+fn read_json_lines<T: for<'de> Deserialize<'de>>(
+    path: &Path
+) -> Result<Vec<T>, CorpusError> {
+    let file = File::open(path).map_err(|e| FileError::new(path, &e))?;
+    let mut values: Vec<T> = vec![];
+    for (line_number, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|e| FileError::new(path, &e))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let value = serde_json::from_str(&line).map_err(|e| {
+            CorpusError::Parse { path: path.to_path_buf(), line: line_number + 1, message: e.to_string() }
+        })?;
+        values.push(value);
+    }
+    Ok(values)
+}
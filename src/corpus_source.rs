@@ -0,0 +1,274 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use crate::Corpus;
+use crate::FileError;
+use crate::SOTUScraper;
+use crate::ScrapeError;
+use crate::ScraperConfig;
+use crate::SotuDocument;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CorpusError {
+    Io(FileError),
+    // A line of a JSON Lines file isn't a valid document
+    Parse { path: PathBuf, line: usize, message: String },
+    Scrape(ScrapeError),
+    // A scrape that gathered some documents but not all of them. The corpus holds the
+    // ones it did gather, so callers can carry on without the rest
+    Incomplete { corpus: Corpus, failures: Vec<ScrapeError> }
+}
+
+impl fmt::Display for CorpusError {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>
+    ) -> fmt::Result {
+        match self {
+            CorpusError::Io(error) => {
+                write!(f, "{}", error)
+            }
+            CorpusError::Parse { path, line, message } => {
+                write!(f, "Couldn't parse line {} of {}: {}", line, path.display(), message)
+            }
+            CorpusError::Scrape(error) => {
+                write!(f, "{}", error)
+            }
+            CorpusError::Incomplete { corpus, failures } => {
+                write!(f, "Gathered {} documents, {} couldn't be gathered", corpus.len(), failures.len())?;
+                match failures.first() {
+                    Some(failure) => write!(f, ", the first: {}", failure),
+                    None => Ok(())
+                }
+            }
+        }
+    }
+}
+
+impl Error for CorpusError {}
+
+impl From<ScrapeError> for CorpusError {
+    fn from(
+        error: ScrapeError
+    ) -> CorpusError {
+        CorpusError::Scrape(error)
+    }
+}
+
+impl From<FileError> for CorpusError {
+    fn from(
+        error: FileError
+    ) -> CorpusError {
+        CorpusError::Io(error)
+    }
+}
+
+// Anywhere documents can come from. Front-ends load a Corpus from a source and train
+// on it, so they don't need to know whether the text was scraped or read from disk
+pub trait CorpusSource {
+    fn load(
+        &self
+    ) -> Result<Corpus, CorpusError>;
+}
+
+// The documents an earlier scrape gathered. Documents it couldn't gather make it
+// an Incomplete error, holding the rest
+impl CorpusSource for SOTUScraper {
+    fn load(
+        &self
+    ) -> Result<Corpus, CorpusError> {
+        if !self.failures().is_empty() {
+            return Err(CorpusError::Incomplete { corpus: self.corpus(), failures: self.failures().to_vec() });
+        }
+        Ok(self.corpus())
+    }
+}
+
+// Scrapes the site when loaded
+impl CorpusSource for ScraperConfig {
+    fn load(
+        &self
+    ) -> Result<Corpus, CorpusError> {
+        SOTUScraper::gather_text_with_config(self)?.load()
+    }
+}
+
+// A text file holding one document, a paragraph per line, like text_samples/biden_sotu_2022.txt.
+// The title is the file name, and the year the last four digit number in it
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextFileSource {
+    pub path: PathBuf
+}
+
+impl TextFileSource {
+    pub fn new(
+        path: impl Into<PathBuf>
+    ) -> TextFileSource {
+        TextFileSource { path: path.into() }
+    }
+}
+
+impl CorpusSource for TextFileSource {
+    fn load(
+        &self
+    ) -> Result<Corpus, CorpusError> {
+        let text = fs::read_to_string(&self.path).map_err(|e| FileError::new(&self.path, &e))?;
+        Ok(Corpus::new(vec![document_from_text(&self.path, &text)]))
+    }
+}
+
+// Every file under a directory whose path matches the pattern, one document per file,
+// in path order. Patterns are path globs relative to the directory, separated by /.
+// * matches any run of characters and ? any one, neither crossing a /, and a ** segment
+// matches any number of subdirectories. So "*.txt" only reads files directly in the
+// directory, "*/*.txt" those one level down and "**/*.txt" every .txt file in the tree
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextDirectorySource {
+    pub directory: PathBuf,
+    pub pattern: String
+}
+
+impl TextDirectorySource {
+    // Every .txt file in the directory
+    pub fn new(
+        directory: impl Into<PathBuf>
+    ) -> TextDirectorySource {
+        TextDirectorySource::with_pattern(directory, "*.txt")
+    }
+
+    pub fn with_pattern(
+        directory: impl Into<PathBuf>,
+        pattern: &str
+    ) -> TextDirectorySource {
+        TextDirectorySource { directory: directory.into(), pattern: pattern.to_string() }
+    }
+}
+
+impl CorpusSource for TextDirectorySource {
+    fn load(
+        &self
+    ) -> Result<Corpus, CorpusError> {
+        let pattern: Vec<&str> = self.pattern.split('/').collect();
+        let mut paths: Vec<PathBuf> = vec![];
+        for path in files_under(&self.directory)? {
+            let segments: Option<Vec<&str>> = path
+                .strip_prefix(&self.directory)
+                .ok()
+                .and_then(|relative| relative.iter().map(|segment| segment.to_str()).collect());
+            if segments.is_some_and(|segments| path_glob_matches(&pattern, &segments)) {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+
+        let mut documents: Vec<SotuDocument> = vec![];
+        for path in paths {
+            let text = fs::read_to_string(&path).map_err(|e| FileError::new(&path, &e))?;
+            documents.push(document_from_text(&path, &text));
+        }
+        Ok(Corpus::new(documents))
+    }
+}
+
+// One document read from standard input, a paragraph per line
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct StdinSource;
+
+impl CorpusSource for StdinSource {
+    fn load(
+        &self
+    ) -> Result<Corpus, CorpusError> {
+        let path = Path::new("<stdin>");
+        let mut text = String::new();
+        io::stdin().lock().read_to_string(&mut text).map_err(|e| FileError::new(path, &e))?;
+        Ok(Corpus::new(vec![document_from_text(path, &text)]))
+    }
+}
+
+fn document_from_text(
+    path: &Path,
+    text: &str
+) -> SotuDocument {
+    let title = path.file_stem().map_or(String::new(), |stem| stem.to_string_lossy().to_string());
+    let year = title
+        .split(|c: char| !c.is_ascii_digit())
+        .rev()
+        .find(|part| part.len() == 4)
+        .and_then(|year| year.parse().ok());
+    let paragraphs = text
+        .lines()
+        .map(|line| line.trim().to_string())
+        .filter(|line| !line.is_empty())
+        .collect();
+    SotuDocument {
+        president: String::new(),
+        date: String::new(),
+        year,
+        title,
+        url: path.display().to_string(),
+        collection: String::new(),
+        categories: vec![],
+        paragraphs
+    }
+}
+
+// Every file in the directory and its subdirectories. Symlinked directories aren't
+// followed, so a link back up the tree can't loop
+fn files_under(
+    directory: &Path
+) -> Result<Vec<PathBuf>, CorpusError> {
+    let mut files: Vec<PathBuf> = vec![];
+    let mut directories = vec![directory.to_path_buf()];
+    while let Some(directory) = directories.pop() {
+        let entries = fs::read_dir(&directory).map_err(|e| FileError::new(&directory, &e))?;
+        for entry in entries {
+            let entry = entry.map_err(|e| FileError::new(&directory, &e))?;
+            let file_type = entry.file_type().map_err(|e| FileError::new(&entry.path(), &e))?;
+            if file_type.is_dir() {
+                directories.push(entry.path());
+            } else if entry.path().is_file() {
+                files.push(entry.path());
+            }
+        }
+    }
+    Ok(files)
+}
+
+// Matches a pattern against a relative path, both split on /. A ** segment matches
+// any number of path segments, including none
+fn path_glob_matches(
+    pattern: &[&str],
+    path: &[&str]
+) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|skipped| path_glob_matches(rest, &path[skipped..])),
+        Some((segment, rest)) => path
+            .split_first()
+            .is_some_and(|(name, path_rest)| glob_matches(segment, name) && path_glob_matches(rest, path_rest))
+    }
+}
+
+fn glob_matches(
+    pattern: &str,
+    name: &str
+) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    // matches[i][j]: the first i characters of the pattern match the first j of the name
+    let mut matches = vec![vec![false; name.len() + 1]; pattern.len() + 1];
+    matches[0][0] = true;
+    for i in 1..=pattern.len() {
+        for j in 0..=name.len() {
+            matches[i][j] = match pattern[i - 1] {
+                '*' => matches[i - 1][j] || (j > 0 && matches[i][j - 1]),
+                '?' => j > 0 && matches[i - 1][j - 1],
+                c => j > 0 && matches[i - 1][j - 1] && name[j - 1] == c
+            };
+        }
+    }
+    matches[pattern.len()][name.len()]
+}
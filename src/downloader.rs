@@ -0,0 +1,110 @@
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::ScrapeError;
+use crate::ScraperConfig;
+
+// Downloads pages for the scraper with one shared client, keeping to the config's
+// rate limit and retrying transient failures. Can be shared between threads
+pub(crate) struct Downloader<'a> {
+    config: &'a ScraperConfig,
+    http_client: reqwest::blocking::Client,
+    // The earliest time the next request may be sent
+    next_request: Mutex<Instant>
+}
+
+impl<'a> Downloader<'a> {
+    pub(crate) fn new(
+        config: &'a ScraperConfig
+    ) -> Result<Downloader<'a>, ScrapeError> {
+        let http_client = reqwest::blocking::Client::builder()
+            .user_agent(config.user_agent.to_string())
+            .build()
+            .map_err(|e| ScrapeError::network(&config.base_url, &e))?;
+        Ok(Downloader {
+            config,
+            http_client,
+            next_request: Mutex::new(Instant::now())
+        })
+    }
+
+    pub(crate) fn get(
+        &self,
+        url: &str
+    ) -> Result<String, ScrapeError> {
+        // Pages the cache can answer by itself don't count towards the rate limit
+        if let Some(cache) = &self.config.cache {
+            if let Some(body) = cache.get_cached(url)? {
+                return Ok(body);
+            }
+        }
+
+        let mut attempt: u32 = 0;
+        loop {
+            self.wait_for_turn();
+            let result = match &self.config.cache {
+                Some(cache) => cache.get_with_client(&self.http_client, url),
+                None => get_text_with_client(&self.http_client, url)
+            };
+            match result {
+                Err(e) if is_transient(&e) && attempt < self.config.max_retries => {
+                    // Exponential backoff, initial_backoff, then twice that, then four times...
+                    let backoff = self.config.initial_backoff * 2_u32.pow(attempt);
+                    println!("Retrying '{url}' in {:?}: {e}", backoff);
+                    thread::sleep(backoff);
+                    attempt += 1;
+                }
+                _ => { return result; }
+            }
+        }
+    }
+
+    // Sleep until this thread may send a request, keeping requests at least
+    // min_request_interval apart across all threads
+    fn wait_for_turn(
+        &self
+    ) {
+        let send_at = {
+            let mut next_request = self.next_request.lock().unwrap();
+            let send_at = (*next_request).max(Instant::now());
+            *next_request = send_at + self.config.min_request_interval;
+            send_at
+        };
+        let wait: Duration = send_at.saturating_duration_since(Instant::now());
+        if !wait.is_zero() {
+            thread::sleep(wait);
+        }
+    }
+}
+
+pub(crate) fn get_text_with_client(
+    http_client: &reqwest::blocking::Client,
+    url: &str
+) -> Result<String, ScrapeError> {
+    let response = http_client
+        // form a get request with get(url)
+        .get(url)
+        // send the request and get Response or else return the error
+        .send()
+        .map_err(|e| ScrapeError::network(url, &e))?;
+
+    // Error pages would otherwise be read as addresses
+    if !response.status().is_success() {
+        return Err(ScrapeError::HttpStatus { url: url.to_string(), status: response.status().as_u16() });
+    }
+
+    // get text from response or else return the error
+    response.text().map_err(|e| ScrapeError::network(url, &e))
+}
+
+// Failures that might not happen again: dropped connections, timeouts, rate limiting and server errors
+fn is_transient(
+    error: &ScrapeError
+) -> bool {
+    match error {
+        ScrapeError::Network { .. } => { true}
+        ScrapeError::HttpStatus { status, .. } => { *status == 429 || *status >= 500}
+        _ => { false}
+    }
+}
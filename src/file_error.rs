@@ -0,0 +1,31 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// A file the scraper, its cache or a corpus source couldn't read or write
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileError {
+    pub path: PathBuf,
+    pub message: String
+}
+
+impl FileError {
+    pub(crate) fn new(
+        path: &Path,
+        error: &io::Error
+    ) -> FileError {
+        FileError { path: path.to_path_buf(), message: error.to_string() }
+    }
+}
+
+impl fmt::Display for FileError {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>
+    ) -> fmt::Result {
+        write!(f, "File {} couldn't be used: {}", self.path.display(), self.message)
+    }
+}
+
+impl Error for FileError {}
@@ -0,0 +1,159 @@
+use rand::Rng;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DecodingStrategy {
+    // Sample the next word from the model's distribution
+    Probabilistic,
+    // Always take the most probable next word, ties broken alphabetically
+    Greedy,
+    // Sample from the distribution sharpened (< 1.0) or flattened (> 1.0) by a temperature
+    Temperature(f64),
+    // Sample from only the k most probable next words
+    TopK(usize),
+    // Sample from the smallest set of most probable words whose probability reaches p
+    TopP(f64)
+}
+
+impl DecodingStrategy {
+    pub fn validate(
+        &self
+    ) -> Result<(), String> {
+        match self {
+            DecodingStrategy::Temperature(temperature) if !(*temperature > 0.0 && temperature.is_finite()) => {
+                Err(format!("Temperature must be positive, got: {:?}", temperature))
+            }
+            DecodingStrategy::TopK(0) => {
+                Err("TopK must keep at least one word".to_string())
+            }
+            DecodingStrategy::TopP(p) if !(*p > 0.0 && *p <= 1.0) => {
+                Err(format!("TopP must be in (0, 1], got: {:?}", p))
+            }
+            _ => { Ok(())}
+        }
+    }
+
+    // Choose the next word from continuations, which must be sorted most probable first
+    pub fn select_word<R: Rng>(
+        &self,
+        continuations: &[(String, f64)],
+        rng: &mut R
+    ) -> Option<String> {
+        if continuations.is_empty() {
+            return None;
+        }
+
+        match self {
+            DecodingStrategy::Greedy => {
+                Some(continuations[0].0.to_string())
+            }
+            DecodingStrategy::Probabilistic => {
+                sample(continuations, rng)
+            }
+            DecodingStrategy::Temperature(temperature) => {
+                // p ^ (1 / T), renormalised when sampling
+                let scaled: Vec<(String, f64)> = continuations
+                    .iter()
+                    .map(|(word, probability)| (word.to_string(), probability.powf(1.0 / temperature)))
+                    .collect();
+                sample(&scaled, rng)
+            }
+            DecodingStrategy::TopK(k) => {
+                let cutoff = (*k).min(continuations.len());
+                sample(&continuations[0..cutoff], rng)
+            }
+            DecodingStrategy::TopP(p) => {
+                let mut accumulated_prob: f64 = 0.0;
+                let mut cutoff = continuations.len();
+                for (i, (_word, probability)) in continuations.iter().enumerate() {
+                    accumulated_prob += probability;
+                    if accumulated_prob >= *p {
+                        cutoff = i + 1;
+                        break;
+                    }
+                }
+                sample(&continuations[0..cutoff], rng)
+            }
+        }
+    }
+}
+
+// Probabilistic, random sampling, the biased dice thing. Weights don't need to sum to 1
+fn sample<R: Rng>(
+    weighted_words: &[(String, f64)],
+    rng: &mut R
+) -> Option<String> {
+    let total_weight: f64 = weighted_words.iter().map(|(_word, weight)| weight).sum();
+    let rand_value = rng.gen::<f64>() * total_weight;
+
+    let mut accumulated_weight: f64 = 0.0;
+    for (word, weight) in weighted_words {
+        accumulated_weight += weight;
+        if accumulated_weight >= rand_value {
+            return Some(word.to_string());
+        }
+    }
+
+    // Floating point error can leave the sum just short of rand_value
+    weighted_words.last().map(|(word, _weight)| word.to_string())
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GenerationConfig {
+    // The end of sentence token is forbidden until this many words have been generated
+    pub min_tokens: usize,
+    pub max_tokens: usize,
+    // Generation stops once the sentence ends with any of these words or phrases
+    pub stop_phrases: Vec<String>,
+    // Forbid any ngram of this size appearing twice in a sentence, 0 disables it.
+    // Stops the degenerate loops of Greedy mode, e.g. "the the the"
+    pub no_repeat_ngram_size: usize
+}
+
+impl Default for GenerationConfig {
+    fn default() -> GenerationConfig {
+        GenerationConfig {
+            min_tokens: 0,
+            max_tokens: 25,
+            stop_phrases: vec![],
+            no_repeat_ngram_size: 0
+        }
+    }
+}
+
+impl GenerationConfig {
+    pub fn validate(
+        &self
+    ) -> Result<(), String> {
+        if self.max_tokens == 0 {
+            return Err("max_tokens must be at least 1".to_string());
+        }
+        if self.min_tokens > self.max_tokens {
+            return Err(format!("min_tokens ({}) is larger than max_tokens ({})", self.min_tokens, self.max_tokens));
+        }
+        Ok(())
+    }
+
+    // Whether word may follow the words generated so far
+    pub fn allows(
+        &self,
+        generated: &[String],
+        word: &str,
+        end_of_sentence: &str
+    ) -> bool {
+        if word == end_of_sentence {
+            return generated.len() >= self.min_tokens;
+        }
+
+        let n = self.no_repeat_ngram_size;
+        if n > 0 && generated.len() >= n - 1 {
+            let tail = &generated[generated.len() + 1 - n..];
+            let repeats = generated
+                .windows(n)
+                .any(|gram| gram[0..n - 1] == *tail && gram[n - 1] == word);
+            if repeats {
+                return false;
+            }
+        }
+        true
+    }
+}
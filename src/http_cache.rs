@@ -0,0 +1,279 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use reqwest::StatusCode;
+use reqwest::header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+
+use crate::FileError;
+use crate::ScrapeError;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CacheError {
+    // Offline mode was asked for a page that was never downloaded
+    MissingPage(String),
+    CorruptEntry(PathBuf)
+}
+
+impl fmt::Display for CacheError {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>
+    ) -> fmt::Result {
+        match self {
+            CacheError::MissingPage(url) => {
+                write!(f, "Page isn't in the cache and downloading is disabled: {}", url)
+            }
+            CacheError::CorruptEntry(path) => {
+                write!(f, "Cache entry can't be read: {}", path.display())
+            }
+        }
+    }
+}
+
+impl Error for CacheError {}
+
+// What is stored next to a cached page, one "key value" pair per line
+#[derive(Clone, Debug, Default, PartialEq)]
+struct CacheEntry {
+    url: String,
+    // Seconds since the unix epoch of the last download or revalidation
+    fetched: u64,
+    etag: Option<String>,
+    last_modified: Option<String>
+}
+
+impl CacheEntry {
+    fn parse(
+        text: &str
+    ) -> Option<CacheEntry> {
+        let mut entry = CacheEntry::default();
+        for line in text.lines() {
+            let (key, value) = line.split_once(' ')?;
+            match key {
+                "url" => { entry.url = value.to_string(); }
+                "fetched" => { entry.fetched = value.parse().ok()?; }
+                "etag" => { entry.etag = Some(value.to_string()); }
+                "last-modified" => { entry.last_modified = Some(value.to_string()); }
+                _ => {}
+            }
+        }
+        Some(entry)
+    }
+
+    fn to_text(
+        &self
+    ) -> String {
+        let mut text = format!("url {}\nfetched {}\n", self.url, self.fetched);
+        if let Some(etag) = &self.etag {
+            text.push_str(&format!("etag {}\n", etag));
+        }
+        if let Some(last_modified) = &self.last_modified {
+            text.push_str(&format!("last-modified {}\n", last_modified));
+        }
+        text
+    }
+}
+
+// A directory of downloaded pages keyed by URL. Pages older than max_age are revalidated
+// with the server using their ETag and Last-Modified headers, and only downloaded again
+// if they changed. In offline mode pages come only from the cache
+#[derive(Clone, Debug, PartialEq)]
+pub struct HttpCache {
+    pub directory: PathBuf,
+    pub max_age: Duration,
+    pub offline: bool
+}
+
+impl HttpCache {
+    pub fn new(
+        directory: impl Into<PathBuf>
+    ) -> HttpCache {
+        HttpCache {
+            directory: directory.into(),
+            max_age: Duration::from_secs(24 * 60 * 60),
+            offline: false
+        }
+    }
+
+    pub fn offline(
+        directory: impl Into<PathBuf>
+    ) -> HttpCache {
+        HttpCache {
+            offline: true,
+            ..HttpCache::new(directory)
+        }
+    }
+
+    pub fn get(
+        &self,
+        url: &str
+    ) -> Result<String, ScrapeError> {
+        if let Some(body) = self.get_cached(url)? {
+            return Ok(body);
+        }
+        self.get_with_client(&reqwest::blocking::Client::new(), url)
+    }
+
+    // The page if it can be answered without the network: it is fresh, or the cache is
+    // offline. None when it has to be downloaded or revalidated
+    pub fn get_cached(
+        &self,
+        url: &str
+    ) -> Result<Option<String>, ScrapeError> {
+        match self.read(url)? {
+            Some((entry, body)) => {
+                let age = now().saturating_sub(entry.fetched);
+                if self.offline || age < self.max_age.as_secs() {
+                    return Ok(Some(body));
+                }
+                Ok(None)
+            }
+            None if self.offline => {
+                Err(ScrapeError::Cache(CacheError::MissingPage(url.to_string())))
+            }
+            None => { Ok(None)}
+        }
+    }
+
+    // Download the page, or revalidate the cached copy, whatever its age
+    pub fn get_with_client(
+        &self,
+        http_client: &reqwest::blocking::Client,
+        url: &str
+    ) -> Result<String, ScrapeError> {
+        if self.offline {
+            return self.get_cached(url)?.ok_or(ScrapeError::Cache(CacheError::MissingPage(url.to_string())));
+        }
+
+        let cached = self.read(url)?;
+        let mut request = http_client.get(url);
+        if let Some((entry, _body)) = &cached {
+            if let Some(etag) = &entry.etag {
+                request = request.header(IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &entry.last_modified {
+                request = request.header(IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+        let response = request.send().map_err(|e| ScrapeError::network(url, &e))?;
+
+        if response.status() == StatusCode::NOT_MODIFIED {
+            if let Some((mut entry, body)) = cached {
+                // Still current, so only the timestamp changes
+                entry.fetched = now();
+                let entry_path = self.entry_path(url);
+                fs::write(&entry_path, entry.to_text()).map_err(|e| FileError::new(&entry_path, &e))?;
+                return Ok(body);
+            }
+        }
+        // Error pages aren't cached
+        if !response.status().is_success() {
+            return Err(ScrapeError::HttpStatus { url: url.to_string(), status: response.status().as_u16() });
+        }
+
+        let header = |name| response.headers().get(name).and_then(|value| value.to_str().ok()).map(str::to_string);
+        let entry = CacheEntry {
+            url: url.to_string(),
+            fetched: now(),
+            etag: header(ETAG),
+            last_modified: header(LAST_MODIFIED)
+        };
+        let body = response.text().map_err(|e| ScrapeError::network(url, &e))?;
+        self.write(&entry, &body)?;
+        Ok(body)
+    }
+
+    // Store a page as if it had just been downloaded, e.g. to seed a cache for offline use
+    pub fn insert(
+        &self,
+        url: &str,
+        body: &str
+    ) -> Result<(), ScrapeError> {
+        let entry = CacheEntry {
+            url: url.to_string(),
+            fetched: now(),
+            ..CacheEntry::default()
+        };
+        self.write(&entry, body)
+    }
+
+    pub fn contains(
+        &self,
+        url: &str
+    ) -> bool {
+        matches!(self.read(url), Ok(Some(_)))
+    }
+
+    fn read(
+        &self,
+        url: &str
+    ) -> Result<Option<(CacheEntry, String)>, ScrapeError> {
+        let entry_path = self.entry_path(url);
+        if !(self.body_path(url).exists() && entry_path.exists()) {
+            return Ok(None);
+        }
+        let entry_text = fs::read_to_string(&entry_path).map_err(|e| FileError::new(&entry_path, &e))?;
+        let entry = match CacheEntry::parse(&entry_text) {
+            Some(entry) => entry,
+            None => { return Err(ScrapeError::Cache(CacheError::CorruptEntry(entry_path))); }
+        };
+        // Files are named by a hash of the URL, so they may belong to another URL with
+        // the same hash. That's a miss, and downloading this page replaces them
+        if entry.url != url {
+            return Ok(None);
+        }
+        let body_path = self.body_path(url);
+        let body = fs::read_to_string(&body_path).map_err(|e| FileError::new(&body_path, &e))?;
+        Ok(Some((entry, body)))
+    }
+
+    fn write(
+        &self,
+        entry: &CacheEntry,
+        body: &str
+    ) -> Result<(), ScrapeError> {
+        fs::create_dir_all(&self.directory).map_err(|e| FileError::new(&self.directory, &e))?;
+        // The body goes first, so a page is only in the cache once both files are written
+        let body_path = self.body_path(&entry.url);
+        fs::write(&body_path, body).map_err(|e| FileError::new(&body_path, &e))?;
+        let entry_path = self.entry_path(&entry.url);
+        fs::write(&entry_path, entry.to_text()).map_err(|e| FileError::new(&entry_path, &e))?;
+        Ok(())
+    }
+
+    fn body_path(
+        &self,
+        url: &str
+    ) -> PathBuf {
+        self.directory.join(format!("{}.html", cache_key(url)))
+    }
+
+    fn entry_path(
+        &self,
+        url: &str
+    ) -> PathBuf {
+        self.directory.join(format!("{}.meta", cache_key(url)))
+    }
+}
+
+// 64 bit FNV-1a of the URL. Unlike std's DefaultHasher it is the same on every
+// platform and Rust version, so a cache directory can be copied between machines
+fn cache_key(
+    url: &str
+) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in url.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("{:016x}", hash)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since_epoch| since_epoch.as_secs())
+}
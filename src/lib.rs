@@ -2,12 +2,48 @@ use std::collections::HashMap;
 use std::fs::File;
 use std::io::{self, BufRead};
 use std::path::Path;
-use rand::Rng;
-
-use select::document::Document;
-use select::predicate::Name;
-use scraper::{Html, Selector};
-use reqwest;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+
+pub mod beam_search;
+pub mod bidirectional;
+pub mod corpus;
+pub mod corpus_archive;
+pub mod corpus_source;
+mod downloader;
+pub mod file_error;
+pub mod generation;
+pub mod http_cache;
+pub mod novelty;
+mod progress_log;
+pub mod query;
+pub mod sotu_document;
+pub mod sotu_scraper;
+pub mod speech;
+pub mod stemmer;
+pub mod stop_words;
+pub mod text_extractor;
+pub mod token_filters;
+pub mod token_stream;
+
+pub use beam_search::{BeamSearchConfig, ConstraintError, LexicalConstraints, ScoredSentence};
+pub use bidirectional::BidirectionalModel;
+pub use corpus::{Corpus, CorpusFilter, DeliveryType, Party};
+pub use corpus_archive::{JsonlSource, TextArchiveSource};
+pub use corpus_source::{CorpusError, CorpusSource, StdinSource, TextDirectorySource, TextFileSource};
+pub use file_error::FileError;
+pub use generation::{DecodingStrategy, GenerationConfig};
+pub use http_cache::{CacheError, HttpCache};
+pub use novelty::{CopiedSpan, NoveltyConfig, NoveltyIndex, NoveltyReport};
+pub use query::{NGramPattern, QueryError, QueryMatch, QueryRanking};
+pub use sotu_document::SotuDocument;
+pub use sotu_scraper::{Collection, SOTUScraper, ScrapeError, ScraperConfig};
+pub use speech::SpeechConfig;
+pub use stop_words::{StopWordExclusion, StopWords};
+pub use text_extractor::{Annotations, TextExtractor};
+pub use token_filters::{Lemmatiser, TokenFilter};
+pub use token_stream::{GeneratedToken, TokenStream};
+use token_filters::apply_token_filters;
 
 pub struct NGramModel {
     pub last_given_penultimate_counts: HashMap<Vec<String>, HashMap<String, i64>>,
@@ -16,7 +52,102 @@ pub struct NGramModel {
     degree: i64,
     start_of_sentence: String,
     end_of_sentence: String,
-    sentence_tokens: Vec<String>
+    paragraph_break: String,
+    sentence_tokens: Vec<String>,
+    token_filters: Vec<TokenFilter>,
+    // A reversed model reads every sentence right to left, see bidirectional.rs
+    reversed: bool,
+    // Tokenised training sentences, only kept once record_source_sentences is called
+    source_sentences: Option<Vec<Vec<String>>>
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Collocation {
+    pub ngram: Vec<String>,
+    pub count: i64,
+    // Pointwise mutual information, log2( P(w1 .. wn) / (P(w1) .. P(wn)) )
+    pub pmi: f64
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RankedNGram {
+    pub ngram: Vec<String>,
+    pub count: i64,
+    pub relative_frequency: f64
+}
+
+// Filters for ngram queries, the default accepts every ngram
+#[derive(Clone, Debug, Default)]
+pub struct NGramFilter {
+    pub exclude_sentence_tokens: bool,
+    pub stop_words: Option<(StopWords, StopWordExclusion)>,
+    // Every word (but not sentence tokens) must be at least this many characters long
+    pub min_word_length: usize,
+    pub containing_word: Option<String>
+}
+
+impl NGramFilter {
+    pub fn new() -> NGramFilter {
+        NGramFilter::default()
+    }
+
+    pub fn without_sentence_tokens(
+        mut self
+    ) -> NGramFilter {
+        self.exclude_sentence_tokens = true;
+        self
+    }
+
+    pub fn with_stop_words(
+        mut self,
+        stop_words: StopWords,
+        exclusion: StopWordExclusion
+    ) -> NGramFilter {
+        self.stop_words = Some((stop_words, exclusion));
+        self
+    }
+
+    pub fn with_min_word_length(
+        mut self,
+        min_word_length: usize
+    ) -> NGramFilter {
+        self.min_word_length = min_word_length;
+        self
+    }
+
+    pub fn containing(
+        mut self,
+        word: &str
+    ) -> NGramFilter {
+        self.containing_word = Some(word.to_string());
+        self
+    }
+
+    pub fn accepts(
+        &self,
+        gram: &[String],
+        sentence_tokens: &[String]
+    ) -> bool {
+        if self.exclude_sentence_tokens && gram.iter().any(|word| sentence_tokens.contains(word)) {
+            return false;
+        }
+        if let Some((stop_words, exclusion)) = &self.stop_words {
+            if stop_words.excludes(gram, *exclusion) {
+                return false;
+            }
+        }
+        if gram.iter()
+            .filter(|word| !sentence_tokens.contains(word))
+            .any(|word| word.chars().count() < self.min_word_length) {
+            return false;
+        }
+        if let Some(containing_word) = &self.containing_word {
+            if !gram.contains(containing_word) {
+                return false;
+            }
+        }
+        true
+    }
 }
 
 impl NGramModel {
@@ -27,13 +158,43 @@ impl NGramModel {
             last_given_penultimate_counts: HashMap::new(),
             penultimate_gram_counts: HashMap::new(),
             ngram_counts: HashMap::new(),
-            degree: degree,
+            degree,
             start_of_sentence: "<S>".to_string(),
             end_of_sentence: "</S>".to_string(),
-            sentence_tokens: vec!["<S>".to_string(), "</S>".to_string()]
+            paragraph_break: "<P>".to_string(),
+            sentence_tokens: vec!["<S>".to_string(), "</S>".to_string(), "<P>".to_string()],
+            token_filters: vec![],
+            reversed: false,
+            source_sentences: None
         }
     }
 
+    // A model of the text read backwards, predicting the word before the history rather
+    // than the one after. All text given to it, for training or as a prompt, is reversed
+    // during tokenisation, so generated words come out last word first
+    pub fn new_reversed(
+        degree: i64
+    ) -> NGramModel {
+        let mut model = NGramModel::new(degree);
+        model.reversed = true;
+        model
+    }
+
+    pub fn is_reversed(
+        &self
+    ) -> bool {
+        self.reversed
+    }
+
+    // Token filters are applied, in order, to every word during tokenisation.
+    // They should be added before the model is trained
+    pub fn add_token_filter(
+        &mut self,
+        filter: TokenFilter
+    ) {
+        self.token_filters.push(filter);
+    }
+
     // TODO: Method for loading and saving (to disk) relevant data structures?
 
     // TODO: Calculate perplexity?
@@ -46,11 +207,11 @@ impl NGramModel {
         match last_given_penultimate_counts.get(&pen_gram) {
             Some(pen_gram_map) => { 
                 match pen_gram_map.get(&last) {
-                    Some(count) => { return *count; }
-                    None => { return 0;}
+                    Some(count) => *count,
+                    None => 0,
                 }
             }
-            None => { return 0; }
+            None => 0,
         }
     }
 
@@ -59,8 +220,8 @@ impl NGramModel {
         pen_gram: Vec<String>
     ) -> i64 {
         match penultimate_gram_counts.get(&pen_gram) {
-            Some(count) => { return *count; }
-            None => { return 0; }
+            Some(count) => *count,
+            None => 0,
         }
     }
 
@@ -109,7 +270,7 @@ impl NGramModel {
     pub fn calculate_ngram_probability(
         penultimate_gram_counts: &HashMap<Vec<String>, i64>,
         last_given_penultimate_counts: &HashMap<Vec<String>, HashMap<String, i64>>,
-        ngram: &Vec<String>
+        ngram: &[String]
     ) -> f64 {
         // To Calculate:
         // For n_gram [A,B,C]
@@ -122,9 +283,9 @@ impl NGramModel {
             
             if penultimate_gram_count.eq(&0) {
                 // Catch a divide by zero to stop it returning NaN
-                return 0 as f64;
+                0 as f64
             } else {
-                return last_given_penultimate_count as f64 / penultimate_gram_count as f64;
+                last_given_penultimate_count as f64 / penultimate_gram_count as f64
             }   
         } else {
             panic!("Split last_mut failed");
@@ -149,13 +310,13 @@ impl NGramModel {
 
         let words: Vec<String> = self.string_to_string_vec(line_of_text);
         if words.len() < self.degree.try_into().unwrap() {
-            return self.probability_for_partial_ngram(&words);
-        } else if words.len() == self.degree.try_into().unwrap() {
-            return NGramModel::calculate_ngram_probability(
+            self.probability_for_partial_ngram(&words)
+        } else if words.len() == usize::try_from(self.degree).unwrap() {
+            NGramModel::calculate_ngram_probability(
                 &self.penultimate_gram_counts,
                 &self.last_given_penultimate_counts,
                 &words
-            );
+            )
         } else {
             // words.len() > self.degree
             // Sum log probabilities at this stage, so as to not incur small floating point number errors
@@ -169,7 +330,7 @@ impl NGramModel {
                     // Store log2 of probability
                     probabilities.push(
                         self.probability_for_partial_ngram(
-                            &words[0..current_size].to_vec()
+                            &words[0..current_size]
                         ).log2()
                     );
                 }
@@ -183,7 +344,7 @@ impl NGramModel {
                     NGramModel::calculate_ngram_probability(
                         &self.penultimate_gram_counts,
                         &self.last_given_penultimate_counts,
-                        &grams.to_vec()
+                        grams
                     ).log2()
                 );
             }
@@ -192,184 +353,273 @@ impl NGramModel {
             let sum_of_log_probs: f64 = probabilities
                 .iter()
                 .sum();
-            return sum_of_log_probs.exp2();
+            sum_of_log_probs.exp2()
         }
     }
 
     fn count_of_partial_ngram(
         &mut self,
-        partial_gram: &Vec<String>
+        partial_gram: &[String]
     ) -> i64 {
         let partial_size = partial_gram.len();
         // Filter ngram_counts for the keys, sliced to partial_size, equal to partial_gram
         // Sum the values and return
-        return self.ngram_counts
+        self.ngram_counts
             .iter()
             .filter(|a|
                     a.0.to_vec()[0..partial_size] == partial_gram.to_vec()
-            ).map(|(_a, b)| b).sum();
+            ).map(|(_a, b)| b).sum()
     }
 
     pub fn probability_for_partial_ngram(
         &mut self,
-        partial_gram: &Vec<String>
+        partial_gram: &[String]
     ) -> f64 {
         assert!(partial_gram.len() < self.degree.try_into().unwrap());
         if partial_gram.len() == 1 {
             // Divide by total number of ngrams
             let total_number_of_ngrams: i64 = self.ngram_counts.values().sum();
-            return self.count_of_partial_ngram(partial_gram) as f64 / total_number_of_ngrams as f64;
+            self.count_of_partial_ngram(partial_gram) as f64 / total_number_of_ngrams as f64
         } else {
             // P(w2|w1) = count(gram[w1, w2]) / count(gram[w1])
             // P(w3|w2, w1) = count(gram[w1, w2, w3]) / count(gram[w1, w2])
             let context_partial_gram = &partial_gram[0..partial_gram.len() - 1].to_vec();
-            return self.count_of_partial_ngram(partial_gram) as f64 / self.count_of_partial_ngram(context_partial_gram) as f64;
+            self.count_of_partial_ngram(partial_gram) as f64 / self.count_of_partial_ngram(context_partial_gram) as f64
         }
     }
 
     fn string_to_string_vec(
-        &mut self,
+        &self,
         line_of_text: String
     ) -> Vec<String>{
         let cleaned_text = line_of_text.replace(&['(', ')', ',', '\"', '.', ';', ':', '\'', '-', '!', '?', '"', '[', ']', '/', '\\'][..], "");
-        return cleaned_text.split_whitespace().map(str::to_string).collect();
+        let mut words: Vec<String> = cleaned_text
+            .split_whitespace()
+            .map(|word| apply_token_filters(&self.token_filters, word))
+            .filter(|word| !word.is_empty())
+            .collect();
+        if self.reversed {
+            words.reverse();
+        }
+        words
     }
 
-    pub fn generate_text(
-        &mut self,
-        generate_mode: String,
-        number_of_sentences: i32
+    pub fn predict_next(
+        &self,
+        history: &str,
+        k: usize
+    ) -> Vec<(String, f64)> {
+        let history_words = self.string_to_string_vec(history.to_string());
+        let context = self.context_from_history(&history_words);
+
+        let mut predictions = self.backoff_continuations(&context);
+        predictions.truncate(k);
+        predictions
+    }
+
+    // Pad the front of a history with start tokens, or keep only its tail, so it is (degree - 1) long
+    fn context_from_history(
+        &self,
+        history: &[String]
     ) -> Vec<String> {
-        if !["Probabilistic", "Greedy"].contains(&&*generate_mode) {
-            panic!("Unrecognised generate_mode supplied: {:?}", generate_mode);
+        let context_size: usize = (self.degree - 1).try_into().unwrap();
+        let mut context: Vec<String> = vec![];
+        for _i in history.len()..context_size {
+            context.push(self.start_of_sentence.to_string());
         }
+        let history_start = history.len().saturating_sub(context_size);
+        context.extend_from_slice(&history[history_start..]);
+        context
+    }
 
-        let mut generated_sentences: Vec<String> = vec![];
-        for _i in 0..number_of_sentences {
-            generated_sentences.push(
-                self.generate_text_individual_sentence(&generate_mode)
-            )
+    // Continuations of the longest tail of the context seen in training, dropping
+    // the oldest word of the context until something is found
+    fn backoff_continuations(
+        &self,
+        context: &[String]
+    ) -> Vec<(String, f64)> {
+        for context_start in 0..=context.len() {
+            let continuations = self.continuations(&context[context_start..]);
+            if !continuations.is_empty() {
+                return continuations;
+            }
         }
-        return generated_sentences;
+        vec![]
     }
 
-    fn generate_text_individual_sentence(
-        &mut self,
-        generate_mode: &String
-    ) -> String {
-        let max_sentence_size = 25;
-        // This will be greedy_based
-        let mut history: Vec<String> = vec![];
-        // Initialise history as (degree - 1) start tokens
-        for _i in 0..(self.degree - 1).try_into().unwrap() {
-            history.push(self.start_of_sentence.to_string());
+    // Every word that followed the context in training with its conditional probability,
+    // sorted most probable first, then alphabetically. The fixed order (rather than
+    // HashMap order) is what makes sampling with a seeded rng reproducible
+    fn continuations(
+        &self,
+        context: &[String]
+    ) -> Vec<(String, f64)> {
+        let context_size = context.len();
+        let mut counts: HashMap<&String, i64> = HashMap::new();
+        if context_size == usize::try_from(self.degree - 1).unwrap() {
+            if let Some(pen_gram_map) = self.last_given_penultimate_counts.get(context) {
+                for (last, count) in pen_gram_map {
+                    counts.insert(last, *count);
+                }
+            }
+        } else {
+            // Lower order, sum over the ngrams starting with the context
+            for (gram, count) in &self.ngram_counts {
+                if gram[0..context_size] == *context {
+                    *counts.entry(&gram[context_size]).or_insert(0) += count;
+                }
+            }
+            // Start tokens are padding, not something to predict
+            counts.remove(&self.start_of_sentence);
         }
 
-        let mut generated_grams_storage: Vec<String> = vec![];
+        let total: i64 = counts.values().sum();
+        let mut continuations: Vec<(String, f64)> = counts
+            .into_iter()
+            .map(|(word, count)| (word.to_string(), count as f64 / total as f64))
+            .collect();
+        continuations.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        continuations
+    }
 
-        let mut generated_gram: String = "".to_string();
-        while generated_gram != self.end_of_sentence &&
-            generated_grams_storage.len() < max_sentence_size {
-            // Keep generating grams based on the history
-            if generate_mode == "Probabilistic" {
-                generated_gram = self.get_most_frequent_gram_prob(&history);
-            } else if generate_mode == "Greedy" {
-                generated_gram = self.get_most_frequent_gram(&history);
-            } else {
-                panic!("Unrecognised generate_mode supplied: {:?}", generate_mode);
-            }
-            
-            // Remove and rotate history, only if history is large enough
-            if self.degree > 1 {
-                history.remove(0);
-                history.push(generated_gram.clone().to_string());
-            }
+    pub fn generate_text(
+        &self,
+        strategy: DecodingStrategy,
+        number_of_sentences: i32
+    ) -> Vec<String> {
+        self.generate_text_with_rng(strategy, number_of_sentences, &mut rand::thread_rng())
+    }
+
+    // The same seed, model and strategy always generate the same sentences
+    pub fn generate_text_seeded(
+        &self,
+        strategy: DecodingStrategy,
+        number_of_sentences: i32,
+        seed: u64
+    ) -> Vec<String> {
+        self.generate_text_with_rng(strategy, number_of_sentences, &mut StdRng::seed_from_u64(seed))
+    }
+
+    pub fn generate_text_with_rng<R: Rng>(
+        &self,
+        strategy: DecodingStrategy,
+        number_of_sentences: i32,
+        rng: &mut R
+    ) -> Vec<String> {
+        self.generate_text_with_config(strategy, number_of_sentences, &GenerationConfig::default(), rng)
+    }
+
+    pub fn generate_text_with_config<R: Rng>(
+        &self,
+        strategy: DecodingStrategy,
+        number_of_sentences: i32,
+        config: &GenerationConfig,
+        rng: &mut R
+    ) -> Vec<String> {
+        NGramModel::validate_generation_arguments(&strategy, config);
 
-            // Track the generated_gram
-            generated_grams_storage.push(generated_gram.clone().to_string())
+        let mut generated_sentences: Vec<String> = vec![];
+        for _i in 0..number_of_sentences {
+            generated_sentences.push(
+                self.generate_text_individual_sentence(&strategy, config, rng)
+            )
         }
+        generated_sentences
+    }
 
-        if generated_grams_storage.last().unwrap().to_string() == self.end_of_sentence {
-            return generated_grams_storage[0..generated_grams_storage.len() - 1].join(" ");
-        } else {
-            return generated_grams_storage.join(" ");
+    fn validate_generation_arguments(
+        strategy: &DecodingStrategy,
+        config: &GenerationConfig
+    ) {
+        if let Err(e) = strategy.validate() {
+            panic!("Invalid decoding strategy supplied: {}", e);
+        }
+        if let Err(e) = config.validate() {
+            panic!("Invalid generation config supplied: {}", e);
         }
     }
 
-    fn get_most_frequent_gram_prob (
-        &mut self,
-        history: &Vec<String>
+    fn generate_text_individual_sentence<R: Rng>(
+        &self,
+        strategy: &DecodingStrategy,
+        config: &GenerationConfig,
+        rng: &mut R
     ) -> String {
-        // probabilistic, random sampling, the biased dice thing
-        let history_size = history.len();
-        assert!(history_size == (self.degree - 1).try_into().unwrap());
-
-        let mut rng = rand::thread_rng();
-        let rand_value = rng.gen::<f64>();
-
-        let mut accumulated_prob: f64 = 0.0;
-
-        for (gram, _k) in &self.ngram_counts {
-            // Shortcircuit for history equals 0
-            if history_size == 0 || gram.to_vec()[0..history_size] == history.to_vec() {
-                accumulated_prob += NGramModel::calculate_ngram_probability(
-                    &self.penultimate_gram_counts,
-                    &self.last_given_penultimate_counts,
-                    &gram
-                );
-                if accumulated_prob >= rand_value {
-                    return gram.last().unwrap().to_string();
-                }
-            }
-        }
+        // Initialise history as (degree - 1) start tokens
+        let history = self.context_from_history(&[]);
+        self.generate_words_from_history(history, strategy, config, rng).join(" ")
+    }
 
-        // TODO: This is 1.0000000000000013 - is this okay?
-        // Sum of suitable ngrams - should be 1
-        // let sum_value: f64 = suitable_ngrams
-        //     .iter()
-        //     .map(|f| self.calculate_ngram_probability(f.0))
-        //     .sum();
+    pub fn generate_from_prompt(
+        &self,
+        prompt: &str,
+        strategy: DecodingStrategy
+    ) -> String {
+        self.generate_from_prompt_with_rng(prompt, strategy, &mut rand::thread_rng())
+    }
 
-        // We should never get here
-        // Return the first from the keys
-        return "".to_string();
+    pub fn generate_from_prompt_with_rng<R: Rng>(
+        &self,
+        prompt: &str,
+        strategy: DecodingStrategy,
+        rng: &mut R
+    ) -> String {
+        self.generate_from_prompt_with_config(prompt, strategy, &GenerationConfig::default(), rng)
     }
 
-    fn get_most_frequent_gram (
-        &mut self,
-        history: &Vec<String>
+    // Token limits in the config count only the generated words, not the prompt
+    pub fn generate_from_prompt_with_config<R: Rng>(
+        &self,
+        prompt: &str,
+        strategy: DecodingStrategy,
+        config: &GenerationConfig,
+        rng: &mut R
     ) -> String {
-        let history_size = history.len();
-        assert!(history_size == (self.degree - 1).try_into().unwrap());
-
-        let mut tracking_max = -1;
-        let mut maximum_end_ngrams: Vec<String> = vec![];
-
-        // Iterate once through ngram_counts to populate maximum_end_ngrams
-        for (gram, k) in &self.ngram_counts {
-            // Shortcircuit for history equals 0
-            if history_size == 0 || gram.to_vec()[0..history_size] == history.to_vec() {
-                // Check if k is at tracking max
-                if k < &tracking_max {
-                    // Do nothing
-                    {}
-                } else if *k == tracking_max {
-                    // Add to maximum_end_ngrams
-                    maximum_end_ngrams.push(gram.last().unwrap().to_string())
-                } else {
-                    // k > tracking_max
-                    tracking_max = *k;
-                    maximum_end_ngrams.clear();
-                    maximum_end_ngrams.push(gram.last().unwrap().to_string())
-                }
+        NGramModel::validate_generation_arguments(&strategy, config);
+
+        // The prompt is tokenised like the training text, and its tail becomes the history.
+        // If that history was never seen, generation backs off to a shorter one
+        let mut words = self.string_to_string_vec(prompt.to_string());
+        let history = self.context_from_history(&words);
+        words.extend(self.generate_words_from_history(history, &strategy, config, rng));
+        words.join(" ")
+    }
+
+    // Generate words until the end of sentence token, which isn't included, a stop phrase or the maximum size
+    fn generate_words_from_history<R: Rng>(
+        &self,
+        history: Vec<String>,
+        strategy: &DecodingStrategy,
+        config: &GenerationConfig,
+        rng: &mut R
+    ) -> Vec<String> {
+        TokenStream::new(self, history, *strategy, config.clone(), rng)
+            .map(|generated| generated.token)
+            .collect()
+    }
+
+    // Like backoff_continuations, but only words the config allows, renormalised.
+    // Backs off further when the config forbids every continuation of a context
+    fn allowed_continuations(
+        &self,
+        history: &[String],
+        generated: &[String],
+        config: &GenerationConfig
+    ) -> Vec<(String, f64)> {
+        for context_start in 0..=history.len() {
+            let allowed: Vec<(String, f64)> = self.continuations(&history[context_start..])
+                .into_iter()
+                .filter(|(word, _probability)| config.allows(generated, word, &self.end_of_sentence))
+                .collect();
+            let total: f64 = allowed.iter().map(|(_word, probability)| probability).sum();
+            if total > 0.0 {
+                return allowed
+                    .into_iter()
+                    .map(|(word, probability)| (word, probability / total))
+                    .collect();
             }
         }
-        
-        // Sort alphabetically
-        maximum_end_ngrams.sort();
-        let most_frequent_gram: String = maximum_end_ngrams.first().unwrap().to_string();
-
-        return most_frequent_gram;
+        vec![]
     }
 
     pub fn update_ngram_model(
@@ -377,6 +627,9 @@ impl NGramModel {
         line_of_text: String
     ) {
         let mut words: Vec<String> = self.string_to_string_vec(line_of_text);
+        if let Some(source_sentences) = &mut self.source_sentences {
+            source_sentences.push(words.clone());
+        }
         
         // Add (degree - 1) start and end tokens to the words
         for _i in 0..(self.degree - 1) {
@@ -384,9 +637,73 @@ impl NGramModel {
             words.push(self.end_of_sentence.to_string());
         }
         
+        self.update_ngram_model_with_words(&words);
+    }
+
+    // Train on a whole document, given as paragraphs of text, keeping context across
+    // sentence boundaries rather than resetting to start tokens for every line.
+    // Sentences are separated by a single end of sentence token, and paragraphs by <P>:
+    //  <S> <S> Last year we were apart </S> This year we are together </S> <P> Tonight </S> </S>
+    // Models trained this way can generate paragraphs and speeches, see speech.rs
+    pub fn update_ngram_model_document(
+        &mut self,
+        paragraphs: &[String]
+    ) {
+        let mut words: Vec<String> = vec![];
+        for _i in 0..(self.degree - 1) {
+            words.push(self.start_of_sentence.to_string());
+        }
+
+        // Sentences are reversed during tokenisation, reversing their order as well reads the whole document backwards
+        let mut ordered_paragraphs: Vec<&String> = paragraphs.iter().collect();
+        if self.reversed {
+            ordered_paragraphs.reverse();
+        }
+
+        let mut first_paragraph = true;
+        for paragraph in ordered_paragraphs {
+            let mut sentences: Vec<Vec<String>> = paragraph
+                .split(['.', '!', '?'])
+                .map(|sentence| self.string_to_string_vec(sentence.to_string()))
+                // Skip leftovers of splitting, like a closing quote after a full stop
+                .filter(|sentence| sentence.iter().any(|word| word.chars().any(char::is_alphanumeric)))
+                .collect();
+            if sentences.is_empty() {
+                continue;
+            }
+            if self.reversed {
+                sentences.reverse();
+            }
+
+            if !first_paragraph {
+                words.push(self.paragraph_break.to_string());
+            }
+            first_paragraph = false;
+            for sentence in sentences {
+                if let Some(source_sentences) = &mut self.source_sentences {
+                    source_sentences.push(sentence.clone());
+                }
+                words.extend(sentence);
+                words.push(self.end_of_sentence.to_string());
+            }
+        }
+
+        // The first end token closes the last sentence, the remaining (degree - 2) end the document.
+        // There is always at least one, so a bigram model still sees an end token follow another
+        for _i in 0..(self.degree - 2).max(1) {
+            words.push(self.end_of_sentence.to_string());
+        }
+        
+        self.update_ngram_model_with_words(&words);
+    }
+
+    fn update_ngram_model_with_words(
+        &mut self,
+        words: &[String]
+    ) {
         // Take a line of text, and update the model with it 
         for ngram in words.windows(self.degree.try_into().unwrap()) {
-            assert!(ngram.len() == self.degree.try_into().unwrap());
+            assert!(ngram.len() == usize::try_from(self.degree).unwrap());
 
             let last = ngram.split_last().unwrap().0;
             let penultimate_gram = ngram.split_last().unwrap().1.to_vec();
@@ -403,16 +720,17 @@ impl NGramModel {
     pub fn most_common_ngram(
         &mut self
     ) -> Result<(&Vec<String>, &i64), &str> {
-        return self.ngram_counts
+        self.ngram_counts
             .iter()
-            .max_by(|a, b| a.1.cmp(&b.1))
-            .ok_or("Couldn't find a bigram");
+            // Break ties alphabetically, so the result doesn't depend on HashMap ordering
+            .max_by(|a, b| a.1.cmp(b.1).then_with(|| b.0.cmp(a.0)))
+            .ok_or("Couldn't find an ngram")
     }
 
     pub fn most_common_ngram_without_sentence_tokens(
         &mut self
     ) -> Result<(&Vec<String>, &i64), &str> {
-        return self.ngram_counts
+        self.ngram_counts
             .iter()
             // Have to iter over all elements of the vector, checking they're not in self.sentence_tokens
             .filter(|a| { 
@@ -421,107 +739,197 @@ impl NGramModel {
                     .count() == 0
                 }
             )
-            .max_by(|a, b| a.1.cmp(&b.1))
-            .ok_or("Couldn't find a bigram");
+            .max_by(|a, b| a.1.cmp(b.1).then_with(|| b.0.cmp(a.0)))
+            .ok_or("Couldn't find an ngram")
     }
-}
 
-pub fn read_lines<P>(filename: P) -> io::Result<io::Lines<io::BufReader<File>>>
-where P: AsRef<Path>, {
-    let file = File::open(filename)?;
-    Ok(io::BufReader::new(file).lines())
-}
+    pub fn top_ngrams(
+        &self,
+        n: usize,
+        filter: &NGramFilter
+    ) -> Vec<RankedNGram> {
+        // Relative frequencies are against every ngram in the model, not just those passing the filter
+        let total_number_of_ngrams: i64 = self.ngram_counts.values().sum();
 
-pub struct SOTUScraper {
-    text_lines: Vec<String>
-}
+        let mut ranked: Vec<(&Vec<String>, &i64)> = self.ngram_counts
+            .iter()
+            .filter(|(gram, _count)| filter.accepts(gram, &self.sentence_tokens))
+            .collect();
+        // Highest count first, ties broken alphabetically
+        ranked.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+
+        ranked
+            .into_iter()
+            .take(n)
+            .map(|(gram, count)| RankedNGram {
+                ngram: gram.to_vec(),
+                count: *count,
+                relative_frequency: *count as f64 / total_number_of_ngrams as f64
+            })
+            .collect()
+    }
 
-impl SOTUScraper {
-    pub fn gather_text() -> SOTUScraper {
-        // Goal: gather lines of text from 
-        let mut sotu_lines: Vec<String> = vec![];
-
-        let directory_url = "https://www.presidency.ucsb.edu/documents/presidential-documents-archive-guidebook/annual-messages-congress-the-state-the-union";
-        let text = SOTUScraper::get_text_from_url(directory_url);
-
-        println!("Downloading SOTU links from UCSB.");
-
-        let mut sotu_links: Vec<String> = vec![];
-
-        match text {
-            Ok(content) => {
-                // Find start of tables
-                let mut table_contents: Vec<String> = vec![];
-                
-                Document::from(content.as_str())
-                    .find(Name("table"))
-                    .for_each(|x| table_contents.push(x.html()));
-
-                // From every table in the document, gather the links to subpages
-                for individual_table in table_contents {
-                    Document::from(individual_table.as_str())
-                        .find(Name("a"))
-                        .filter_map(|n| n.attr("href"))
-                        .filter(|a| a.contains("https://www.presidency.ucsb.edu/documents/"))
-                        .for_each(|x| sotu_links.push(x.to_string()));
+    pub fn most_common_ngram_excluding_stop_words(
+        &self,
+        stop_words: &StopWords,
+        exclusion: StopWordExclusion
+    ) -> Result<(&Vec<String>, &i64), &str> {
+        self.ngram_counts
+            .iter()
+            .filter(|a| !a.0.iter().any(|gram| self.sentence_tokens.contains(gram)))
+            .filter(|a| !stop_words.excludes(a.0, exclusion))
+            // Break ties alphabetically, so the result doesn't depend on HashMap ordering
+            .max_by(|a, b| a.1.cmp(b.1).then_with(|| b.0.cmp(a.0)))
+            .ok_or("Couldn't find an ngram")
+    }
+
+    pub fn collocation_report(
+        &self,
+        min_count: i64,
+        stop_words: Option<(&StopWords, StopWordExclusion)>
+    ) -> Vec<Collocation> {
+        // Every word starts exactly one ngram (the end is padded with </S>),
+        // so counting the first word of each ngram gives the unigram counts
+        let total_number_of_ngrams: i64 = self.ngram_counts.values().sum();
+        let mut unigram_counts: HashMap<&String, i64> = HashMap::new();
+        for (gram, count) in &self.ngram_counts {
+            *unigram_counts.entry(&gram[0]).or_insert(0) += count;
+        }
+
+        let mut collocations: Vec<Collocation> = self.ngram_counts
+            .iter()
+            .filter(|(gram, count)| {
+                **count >= min_count &&
+                    !gram.iter().any(|word| self.sentence_tokens.contains(word))
+            })
+            .filter(|(gram, _count)| {
+                match stop_words {
+                    Some((stop_words, exclusion)) => !stop_words.excludes(gram, exclusion),
+                    None => true
                 }
-            }
-            Err(e) => panic!("Failed to get text: {e:?}")
-        }
-
-        sotu_links.sort_unstable();
-        sotu_links.dedup();
-        let total_links = sotu_links.len();
-        println!("We have gathered {:?} SOTU links.", total_links);
-
-        // Build selector for SOTU content
-        let selector = Selector::parse(r#"div[class="field-docs-content"]"#).unwrap();
-
-        // For every subpage, get the HTML and parse it correctly
-        for (pos, sotu_link) in sotu_links.iter().enumerate() {
-            println!("{:?}/{total_links} -- Downloading: '{sotu_link}'", pos+1);
-            let sotu_text = SOTUScraper::get_text_from_url(&sotu_link);
-            match sotu_text {
-                Ok(content) => {
-                    let fragment = Html::parse_fragment(&content);
-                    let ul = fragment.select(&selector).next().unwrap();
-                    let text_lines = ul.child_elements().flat_map(|el| el.text()).collect::<Vec<_>>();
-                    
-                    // Got the content and loading it into the model
-                    for line in text_lines {
-                        for part_line in line.split(".") {
-                            if !part_line.is_empty() {
-                                let cleaned_line = part_line.trim().to_string();
-                                sotu_lines.push(cleaned_line);
-                            }
-                        }
-                    }
+            })
+            .map(|(gram, count)| {
+                let ngram_probability = *count as f64 / total_number_of_ngrams as f64;
+                let independent_probability: f64 = gram
+                    .iter()
+                    .map(|word| *unigram_counts.get(word).unwrap_or(&0) as f64 / total_number_of_ngrams as f64)
+                    .product();
+                Collocation {
+                    ngram: gram.to_vec(),
+                    count: *count,
+                    pmi: (ngram_probability / independent_probability).log2()
                 }
-                Err(e) => panic!("Failed to get text: {e:?}")
-            }
+            })
+            .collect();
+
+        collocations.sort_by(|a, b| {
+            b.pmi.total_cmp(&a.pmi)
+                .then_with(|| b.count.cmp(&a.count))
+                .then_with(|| a.ngram.cmp(&b.ngram))
+        });
+        collocations
+    }
+
+    // Counts of the lower order grams of the given size. Every word starts exactly
+    // one ngram, so these are the counts of the ngram prefixes
+    fn prefix_counts(
+        &self,
+        size: usize
+    ) -> HashMap<Vec<String>, i64> {
+        let mut counts: HashMap<Vec<String>, i64> = HashMap::new();
+        for (gram, count) in &self.ngram_counts {
+            *counts.entry(gram[0..size].to_vec()).or_insert(0) += count;
         }
-        
-        return SOTUScraper {text_lines: sotu_lines}
+        counts
     }
 
-    fn get_text_from_url(url: &str) -> Result<String, Box<dyn std::error::Error>> {
-        let http_client = reqwest::blocking::Client::new();
-        
-        let response = http_client
-            // form a get request with get(url)
-            .get(url)
-            // send the request and get Response or else return the error
-            .send()?
-            // get text from response or else return the error
-            .text()?;
+    pub fn query(
+        &self,
+        pattern: &str,
+        ranking: QueryRanking
+    ) -> Result<Vec<QueryMatch>, QueryError> {
+        let degree: usize = self.degree.try_into().unwrap();
+        // Literal words go through the same token filters as the training text
+        let pattern = NGramPattern::parse(pattern)?.map_literals(|word| {
+            if self.sentence_tokens.iter().any(|token| token == word) {
+                word.to_string()
+            } else {
+                apply_token_filters(&self.token_filters, word)
+            }
+        });
+        if pattern.len() > degree {
+            return Err(QueryError::PatternTooLong { pattern_length: pattern.len(), degree });
+        }
 
-        // wrapped response in Result
-        Ok(response)
+        // Patterns shorter than the degree are matched against the lower order counts
+        let counts = self.prefix_counts(pattern.len());
+        let context_counts = if pattern.len() > 1 {
+            self.prefix_counts(pattern.len() - 1)
+        } else {
+            HashMap::new()
+        };
+        let total_number_of_ngrams: i64 = self.ngram_counts.values().sum();
+
+        let mut matches: Vec<QueryMatch> = counts
+            .iter()
+            .filter(|(gram, _count)| pattern.matches(gram))
+            .map(|(gram, count)| {
+                let context_count = if gram.len() > 1 {
+                    *context_counts.get(&gram[0..gram.len() - 1]).unwrap_or(&0)
+                } else {
+                    total_number_of_ngrams
+                };
+                QueryMatch {
+                    ngram: gram.to_vec(),
+                    count: *count,
+                    probability: *count as f64 / context_count as f64
+                }
+            })
+            .collect();
+
+        // Ties are broken by count, then alphabetically
+        matches.sort_by(|a, b| {
+            let ranked = match ranking {
+                QueryRanking::Count => b.count.cmp(&a.count),
+                QueryRanking::ConditionalProbability => b.probability.total_cmp(&a.probability)
+            };
+            ranked
+                .then_with(|| b.count.cmp(&a.count))
+                .then_with(|| a.ngram.cmp(&b.ngram))
+        });
+        Ok(matches)
     }
 
-    pub fn get_line_iterator(
-        &mut self
-    ) -> std::slice::Iter<'_, String> {
-        return self.text_lines.iter();
+    pub fn most_common_ngram_with_token_filters(
+        &self,
+        filters: &[TokenFilter]
+    ) -> Result<(Vec<String>, i64), &str> {
+        // Collapse the ngram counts through the filters, so that ngrams sharing a stem are counted together
+        let mut filtered_counts: HashMap<Vec<String>, i64> = HashMap::new();
+        for (gram, count) in &self.ngram_counts {
+            let filtered_gram: Vec<String> = gram
+                .iter()
+                .map(|word| {
+                    if self.sentence_tokens.contains(word) {
+                        word.to_string()
+                    } else {
+                        apply_token_filters(filters, word)
+                    }
+                })
+                .collect();
+            *filtered_counts.entry(filtered_gram).or_insert(0) += count;
+        }
+
+        // Break ties alphabetically, so the result doesn't depend on HashMap ordering
+        filtered_counts
+            .into_iter()
+            .max_by(|a, b| a.1.cmp(&b.1).then_with(|| b.0.cmp(&a.0)))
+            .ok_or("Couldn't find an ngram")
     }
 }
+
+pub fn read_lines<P>(filename: P) -> io::Result<io::Lines<io::BufReader<File>>>
+where P: AsRef<Path>, {
+    let file = File::open(filename)?;
+    Ok(io::BufReader::new(file).lines())
+}
@@ -1,23 +1,82 @@
 use std::env;
+use std::path::Path;
+use std::process;
 
+use grams::DecodingStrategy;
 use grams::NGramModel;
-use grams::SOTUScraper;
+use grams::{Collection, ScraperConfig};
+use grams::{CorpusError, CorpusSource, StdinSource, TextDirectorySource, TextFileSource};
+use grams::{JsonlSource, TextArchiveSource};
+
+const USAGE: &str = "Usage: grams <sotu | ucsb | - | corpus.jsonl | archive directory | text directory | text file>";
+
+// "sotu" scrapes the UCSB addresses, "ucsb" those and the inaugural and farewell
+// addresses, "-" reads standard input, and otherwise the
+// argument is an exported .jsonl corpus, a text archive, a text file or a directory of .txt files.
+// Nothing is read or scraped until the source is loaded
+fn corpus_source(
+    argument: &str
+) -> Result<Box<dyn CorpusSource>, String> {
+    if argument == "sotu" || argument == "ucsb" {
+        let mut config = ScraperConfig::default();
+        if argument == "ucsb" {
+            config.collections = vec![
+                Collection::state_of_the_union(),
+                Collection::inaugural_addresses(),
+                Collection::farewell_addresses()
+            ];
+        }
+        return Ok(Box::new(config));
+    }
+    if argument == "-" {
+        return Ok(Box::new(StdinSource));
+    }
+    let path = Path::new(argument);
+    if path.is_dir() && path.join("index.jsonl").is_file() {
+        return Ok(Box::new(TextArchiveSource::new(argument)));
+    }
+    if path.is_dir() {
+        return Ok(Box::new(TextDirectorySource::new(argument)));
+    }
+    if !path.is_file() {
+        return Err(format!("{:?} is neither a source name nor an existing file or directory", argument));
+    }
+    if path.extension().is_some_and(|extension| extension == "jsonl") {
+        return Ok(Box::new(JsonlSource::new(argument)));
+    }
+    Ok(Box::new(TextFileSource::new(argument)))
+}
 
 fn main() {
     // Get program arguments
     let args: Vec<String> = env::args().collect();
     if args.len() != 2 {
-        panic!("Not enough program arguments supplied, you supplied: {:?}", args);
+        eprintln!("{}", USAGE);
+        process::exit(2);
     }
+    let source = match corpus_source(&args[1]) {
+        Ok(source) => source,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            process::exit(2);
+        }
+    };
 
     // Create an instance of the NGramModel
     let mut ngmodel = NGramModel::new(4);
 
-    let mut sotu_scraper = SOTUScraper::gather_text();
-
-    for sotu_line in sotu_scraper.get_line_iterator() {
-        ngmodel.update_ngram_model(sotu_line.to_string());
-    }
+    let corpus = match source.load() {
+        Ok(corpus) => corpus,
+        Err(CorpusError::Incomplete { corpus, failures }) => {
+            println!("Skipped {:?} documents that couldn't be gathered.", failures.len());
+            corpus
+        }
+        Err(e) => {
+            eprintln!("Failed to load the corpus: {}", e);
+            process::exit(1);
+        }
+    };
+    corpus.train(&mut ngmodel);
 
     let most_common_ngram_result = ngmodel.most_common_ngram_without_sentence_tokens();
     
@@ -29,7 +88,7 @@ fn main() {
 
     // Generate 10 sample sentences
     println!("I generated some sample sentences for you:");
-    for generated_sentence in ngmodel.generate_text("Probabilistic".to_string(), 10) {
+    for generated_sentence in ngmodel.generate_text(DecodingStrategy::Probabilistic, 10) {
         println!("\t{:?}.",
             generated_sentence
         );
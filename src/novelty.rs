@@ -0,0 +1,222 @@
+use std::collections::HashMap;
+
+use rand::Rng;
+
+use crate::DecodingStrategy;
+use crate::GenerationConfig;
+use crate::NGramModel;
+
+// Ids that never belong to a word: the end of each indexed sentence, so matches can't
+// run from one sentence into the next, and any generated word not in the corpus
+const SENTENCE_SEPARATOR: u32 = u32::MAX - 1;
+const UNKNOWN_WORD: u32 = u32::MAX;
+
+// A run of words in a generated sentence that appears verbatim in a training sentence
+#[derive(Clone, Debug, PartialEq)]
+pub struct CopiedSpan {
+    pub words: Vec<String>,
+    // Index of the first copied word in the generated sentence
+    pub position: usize,
+    // Index into the model's source sentences, and of the first copied word within it
+    pub source_sentence: usize,
+    pub source_position: usize
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct NoveltyReport {
+    pub sentence_length: usize,
+    // The longest run copied from the corpus, None when no word of the sentence appears in it
+    pub longest_copy: Option<CopiedSpan>,
+    // Length of the longest copied run over the sentence length, 1.0 is a verbatim copy
+    pub copy_ratio: f64
+}
+
+impl NoveltyReport {
+    pub fn is_verbatim(
+        &self
+    ) -> bool {
+        self.sentence_length > 0 && self.copy_ratio == 1.0
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct NoveltyConfig {
+    // Sentences whose copy_ratio is above this are rejected and generated again
+    pub max_copy_ratio: f64,
+    // Attempts at each sentence before giving up on it
+    pub max_attempts: usize
+}
+
+impl Default for NoveltyConfig {
+    fn default() -> NoveltyConfig {
+        NoveltyConfig {
+            max_copy_ratio: 0.8,
+            max_attempts: 10
+        }
+    }
+}
+
+// A suffix array over the source sentences, for finding the longest run of a generated
+// sentence that was copied from the training text. Borrows the sentences it indexes
+pub struct NoveltyIndex<'a> {
+    sentences: &'a [Vec<String>],
+    word_ids: HashMap<String, u32>,
+    // Every source sentence as word ids, each followed by SENTENCE_SEPARATOR
+    tokens: Vec<u32>,
+    // Where each sentence starts in tokens
+    sentence_starts: Vec<usize>,
+    // Start of every suffix of tokens, in sorted order
+    suffix_array: Vec<usize>
+}
+
+impl<'a> NoveltyIndex<'a> {
+    pub fn new(
+        sentences: &'a [Vec<String>]
+    ) -> NoveltyIndex<'a> {
+        let mut word_ids: HashMap<String, u32> = HashMap::new();
+        let mut tokens: Vec<u32> = vec![];
+        let mut sentence_starts: Vec<usize> = vec![];
+        for sentence in sentences {
+            sentence_starts.push(tokens.len());
+            for word in sentence {
+                let next_id = word_ids.len() as u32;
+                tokens.push(*word_ids.entry(word.to_string()).or_insert(next_id));
+            }
+            tokens.push(SENTENCE_SEPARATOR);
+        }
+
+        let mut suffix_array: Vec<usize> = (0..tokens.len()).collect();
+        suffix_array.sort_by(|a, b| tokens[*a..].cmp(&tokens[*b..]));
+
+        NoveltyIndex {
+            sentences,
+            word_ids,
+            tokens,
+            sentence_starts,
+            suffix_array
+        }
+    }
+
+    // Compare a generated sentence, as the space separated words generation returns, against the corpus
+    pub fn check(
+        &self,
+        sentence: &str
+    ) -> NoveltyReport {
+        let words: Vec<String> = sentence.split_whitespace().map(str::to_string).collect();
+        let ids: Vec<u32> = words
+            .iter()
+            .map(|word| *self.word_ids.get(word).unwrap_or(&UNKNOWN_WORD))
+            .collect();
+
+        // (length, position in the sentence, position in tokens) of the longest run
+        let mut longest: (usize, usize, usize) = (0, 0, 0);
+        for position in 0..ids.len() {
+            let (length, token_position) = self.longest_prefix_match(&ids[position..]);
+            if length > longest.0 {
+                longest = (length, position, token_position);
+            }
+        }
+
+        let (length, position, token_position) = longest;
+        let longest_copy = if length > 0 {
+            let source_sentence = self.sentence_starts.partition_point(|start| *start <= token_position) - 1;
+            Some(CopiedSpan {
+                words: words[position..position + length].to_vec(),
+                position,
+                source_sentence,
+                source_position: token_position - self.sentence_starts[source_sentence]
+            })
+        } else {
+            None
+        };
+        let copy_ratio = if words.is_empty() { 0.0 } else { length as f64 / words.len() as f64 };
+        NoveltyReport {
+            sentence_length: words.len(),
+            longest_copy,
+            copy_ratio
+        }
+    }
+
+    pub fn source_sentence(
+        &self,
+        index: usize
+    ) -> Option<&[String]> {
+        self.sentences.get(index).map(|sentence| sentence.as_slice())
+    }
+
+    // The longest prefix of query found anywhere in tokens, and where. It is shared with
+    // one of the two suffixes either side of where query would sort in the suffix array
+    fn longest_prefix_match(
+        &self,
+        query: &[u32]
+    ) -> (usize, usize) {
+        let insertion_point = self.suffix_array.partition_point(|start| self.tokens[*start..] < *query);
+
+        let mut best: (usize, usize) = (0, 0);
+        for neighbour in [insertion_point.checked_sub(1), Some(insertion_point)].into_iter().flatten() {
+            if let Some(start) = self.suffix_array.get(neighbour) {
+                let length = self.tokens[*start..]
+                    .iter()
+                    .zip(query)
+                    .take_while(|(token, word)| token == word)
+                    .count();
+                if length > best.0 {
+                    best = (length, *start);
+                }
+            }
+        }
+        best
+    }
+}
+
+impl NGramModel {
+    // Keep every tokenised training sentence from now on, for novelty checks.
+    // Should be called before the model is trained
+    pub fn record_source_sentences(
+        &mut self
+    ) {
+        if self.source_sentences.is_none() {
+            self.source_sentences = Some(vec![]);
+        }
+    }
+
+    pub fn source_sentences(
+        &self
+    ) -> Option<&[Vec<String>]> {
+        self.source_sentences.as_deref()
+    }
+
+    // None unless source sentences were recorded
+    pub fn novelty_index(
+        &self
+    ) -> Option<NoveltyIndex<'_>> {
+        self.source_sentences.as_deref().map(NoveltyIndex::new)
+    }
+
+    // Like generate_text_with_config, but sentences copied from the corpus beyond the
+    // config's max_copy_ratio are generated again. A sentence still too close to the
+    // corpus after max_attempts is left out, so fewer sentences may be returned
+    pub fn generate_novel_text_with_rng<R: Rng>(
+        &self,
+        strategy: DecodingStrategy,
+        number_of_sentences: i32,
+        index: &NoveltyIndex<'_>,
+        novelty_config: &NoveltyConfig,
+        rng: &mut R
+    ) -> Vec<String> {
+        let config = GenerationConfig::default();
+        NGramModel::validate_generation_arguments(&strategy, &config);
+
+        let mut generated_sentences: Vec<String> = vec![];
+        for _i in 0..number_of_sentences {
+            for _attempt in 0..novelty_config.max_attempts {
+                let sentence = self.generate_text_individual_sentence(&strategy, &config, rng);
+                if index.check(&sentence).copy_ratio <= novelty_config.max_copy_ratio {
+                    generated_sentences.push(sentence);
+                    break;
+                }
+            }
+        }
+        generated_sentences
+    }
+}
@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::FileError;
+use crate::ScrapeError;
+use crate::SotuDocument;
+
+// Every document a scrape has finished, appended as soon as it is done so an interrupted
+// scrape can carry on from where it stopped. A document is a block of "key value" lines
+// closed by "end", with newlines and backslashes in values escaped:
+//  url https://www.presidency.ucsb.edu/documents/...
+//  president Joseph R. Biden, Jr.
+//  ...
+//  paragraph Last year COVID-19 kept us apart.
+//  end
+pub(crate) struct ProgressLog {
+    path: PathBuf,
+    file: Mutex<File>
+}
+
+impl ProgressLog {
+    // Opens the log for appending, and returns the documents it already holds by URL
+    pub(crate) fn open(
+        path: &Path
+    ) -> Result<(ProgressLog, HashMap<String, SotuDocument>), ScrapeError> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => { return Err(FileError::new(path, &e).into()); }
+        };
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| FileError::new(path, &e))?;
+        // Whatever follows the last finished document was cut off, and is dropped so the
+        // next document doesn't carry on from partway through its last line
+        let finished_length = text.rfind("\nend\n").map_or(0, |position| position + "\nend\n".len());
+        if finished_length < text.len() {
+            file.set_len(finished_length as u64).map_err(|e| FileError::new(path, &e))?;
+        }
+        let finished = parse_documents(&text[..finished_length]);
+        let progress_log = ProgressLog {
+            path: path.to_path_buf(),
+            file: Mutex::new(file)
+        };
+        Ok((progress_log, finished))
+    }
+
+    pub(crate) fn append(
+        &self,
+        document: &SotuDocument
+    ) -> Result<(), ScrapeError> {
+        let mut record = format!(
+            "url {}\npresident {}\ndate {}\n",
+            escape(&document.url), escape(&document.president), escape(&document.date)
+        );
+        if let Some(year) = document.year {
+            record.push_str(&format!("year {}\n", year));
+        }
+        record.push_str(&format!("title {}\n", escape(&document.title)));
+        record.push_str(&format!("collection {}\n", escape(&document.collection)));
+        for category in &document.categories {
+            record.push_str(&format!("category {}\n", escape(category)));
+        }
+        for paragraph in &document.paragraphs {
+            record.push_str(&format!("paragraph {}\n", escape(paragraph)));
+        }
+        record.push_str("end\n");
+
+        // One write per document, so documents from different threads don't interleave
+        let mut file = self.file.lock().unwrap();
+        file
+            .write_all(record.as_bytes())
+            .and_then(|_| file.flush())
+            .map_err(|e| FileError::new(&self.path, &e).into())
+    }
+}
+
+// Blocks without their "end", e.g. cut off by a crash, are dropped
+fn parse_documents(
+    text: &str
+) -> HashMap<String, SotuDocument> {
+    let mut documents: HashMap<String, SotuDocument> = HashMap::new();
+    let mut document = new_document();
+    for line in text.lines() {
+        if line == "end" {
+            let finished = std::mem::replace(&mut document, new_document());
+            if !finished.url.is_empty() {
+                documents.insert(finished.url.to_string(), finished);
+            }
+            continue;
+        }
+        let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+        let value = unescape(value);
+        match key {
+            "url" => {
+                // A new block before the last one ended, start again
+                document = new_document();
+                document.url = value;
+            }
+            "president" => { document.president = value; }
+            "date" => { document.date = value; }
+            "year" => { document.year = value.parse().ok(); }
+            "title" => { document.title = value; }
+            "collection" => { document.collection = value; }
+            "category" => { document.categories.push(value); }
+            "paragraph" => { document.paragraphs.push(value); }
+            _ => {}
+        }
+    }
+    documents
+}
+
+fn new_document() -> SotuDocument {
+    SotuDocument {
+        president: String::new(),
+        date: String::new(),
+        year: None,
+        title: String::new(),
+        url: String::new(),
+        collection: String::new(),
+        categories: vec![],
+        paragraphs: vec![]
+    }
+}
+
+fn escape(
+    value: &str
+) -> String {
+    value.replace('\\', "\\\\").replace('\n', "\\n").replace('\r', "\\r")
+}
+
+fn unescape(
+    value: &str
+) -> String {
+    let mut unescaped = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => unescaped.push('\n'),
+            Some('r') => unescaped.push('\r'),
+            Some(other) => unescaped.push(other),
+            None => {}
+        }
+    }
+    unescaped
+}
@@ -0,0 +1,136 @@
+use std::error::Error;
+use std::fmt;
+
+// A query pattern is a whitespace separated list of terms, each one of:
+//  *             matches any single token
+//  word          matches the token exactly
+//  (we|they|I)   matches any of the alternatives, the brackets are optional
+// e.g. "the * of", "we will *", "(we|they) will *"
+#[derive(Clone, Debug, PartialEq)]
+pub enum PatternTerm {
+    Wildcard,
+    Alternatives(Vec<String>)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct NGramPattern {
+    pub terms: Vec<PatternTerm>
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QueryRanking {
+    Count,
+    // P(last word | preceding words of the match)
+    ConditionalProbability
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct QueryMatch {
+    pub ngram: Vec<String>,
+    pub count: i64,
+    pub probability: f64
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum QueryError {
+    EmptyPattern,
+    InvalidTerm(String),
+    PatternTooLong { pattern_length: usize, degree: usize }
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>
+    ) -> fmt::Result {
+        match self {
+            QueryError::EmptyPattern => { write!(f, "Query pattern is empty")}
+            QueryError::InvalidTerm(term) => { write!(f, "Invalid query term: {:?}", term)}
+            QueryError::PatternTooLong { pattern_length, degree } => {
+                write!(f, "Query pattern has {} terms, but the model degree is {}", pattern_length, degree)
+            }
+        }
+    }
+}
+
+impl Error for QueryError {}
+
+impl NGramPattern {
+    pub fn parse(
+        pattern: &str
+    ) -> Result<NGramPattern, QueryError> {
+        let mut terms: Vec<PatternTerm> = vec![];
+        for term in pattern.split_whitespace() {
+            if term == "*" {
+                terms.push(PatternTerm::Wildcard);
+                continue;
+            }
+
+            let inner = match (term.starts_with('('), term.ends_with(')')) {
+                (true, true) => &term[1..term.len() - 1],
+                (false, false) => term,
+                _ => { return Err(QueryError::InvalidTerm(term.to_string())); }
+            };
+            let alternatives: Vec<String> = inner.split('|').map(str::to_string).collect();
+            if alternatives.iter().any(|a| a.is_empty() || a.contains(['*', '(', ')'])) {
+                return Err(QueryError::InvalidTerm(term.to_string()));
+            }
+            terms.push(PatternTerm::Alternatives(alternatives));
+        }
+
+        if terms.is_empty() {
+            return Err(QueryError::EmptyPattern);
+        }
+        Ok(NGramPattern { terms })
+    }
+
+    pub fn len(
+        &self
+    ) -> usize {
+        self.terms.len()
+    }
+
+    pub fn is_empty(
+        &self
+    ) -> bool {
+        self.terms.is_empty()
+    }
+
+    pub fn matches(
+        &self,
+        gram: &[String]
+    ) -> bool {
+        if gram.len() != self.terms.len() {
+            return false;
+        }
+        self.terms
+            .iter()
+            .zip(gram)
+            .all(|(term, word)| {
+                match term {
+                    PatternTerm::Wildcard => true,
+                    PatternTerm::Alternatives(alternatives) => alternatives.contains(word)
+                }
+            })
+    }
+
+    // Apply a transformation (e.g. the model's token filters) to every literal in the pattern
+    pub fn map_literals<F>(
+        self,
+        f: F
+    ) -> NGramPattern
+    where F: Fn(&str) -> String, {
+        let terms = self.terms
+            .into_iter()
+            .map(|term| {
+                match term {
+                    PatternTerm::Wildcard => PatternTerm::Wildcard,
+                    PatternTerm::Alternatives(alternatives) => {
+                        PatternTerm::Alternatives(alternatives.iter().map(|a| f(a)).collect())
+                    }
+                }
+            })
+            .collect();
+        NGramPattern { terms }
+    }
+}
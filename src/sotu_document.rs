@@ -0,0 +1,109 @@
+use scraper::{ElementRef, Html, Selector};
+use serde::{Deserialize, Serialize};
+
+use crate::ScrapeError;
+use crate::ScraperConfig;
+
+// One address, with who gave it and when
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SotuDocument {
+    pub president: String,
+    // As the page shows it, e.g. "March 01, 2022"
+    pub date: String,
+    pub year: Option<i32>,
+    pub title: String,
+    pub url: String,
+    // Name of the scraper collection the document came from, e.g. "inaugural", empty
+    // for documents read from text files. Corpora exported before collections existed have none
+    #[serde(default)]
+    pub collection: String,
+    // The site's categories for the document, e.g. "Spoken Addresses and Remarks" or
+    // "Written Messages", empty where the page or file had none
+    #[serde(default)]
+    pub categories: Vec<String>,
+    pub paragraphs: Vec<String>
+}
+
+impl SotuDocument {
+    // Read an address from its page, failing if the page has no content element.
+    // Missing metadata is left empty rather than failing the whole document. The scraper
+    // fills in the collection
+    pub fn from_html(
+        url: &str,
+        html: &str,
+        config: &ScraperConfig
+    ) -> Result<SotuDocument, ScrapeError> {
+        let page = Html::parse_document(html);
+
+        let content = match page.select(&parse_selector(&config.content_selector)?).next() {
+            Some(content) => content,
+            None => { return Err(ScrapeError::MissingContent { url: url.to_string() }); }
+        };
+        let paragraphs = config.text_extractor.extract(content);
+
+        let mut categories: Vec<String> = vec![];
+        for category in page.select(&parse_selector(&config.category_selector)?).map(element_text) {
+            if !category.is_empty() && !categories.contains(&category) {
+                categories.push(category);
+            }
+        }
+
+        let date_selector = parse_selector(&config.date_selector)?;
+        let date_element = page.select(&date_selector).next();
+        let date = date_element.map_or(String::new(), element_text);
+        // The machine readable date if there is one, e.g. content="2022-03-01T21:00:00+00:00",
+        // otherwise the last four digit number in the displayed date
+        let year = date_element
+            .and_then(|el| el.value().attr("content"))
+            .and_then(|content| content.get(0..4))
+            .and_then(|year| year.parse().ok())
+            .or_else(|| date.split(|c: char| !c.is_ascii_digit()).rev().find(|part| part.len() == 4)?.parse().ok());
+
+        Ok(SotuDocument {
+            president: first_text(&page, &config.president_selector)?,
+            date,
+            year,
+            title: first_text(&page, &config.title_selector)?,
+            url: url.to_string(),
+            collection: String::new(),
+            categories,
+            paragraphs
+        })
+    }
+
+    // The text as lines, split at full stops, the way the model is trained on it
+    pub fn lines(
+        &self
+    ) -> Vec<String> {
+        let mut lines: Vec<String> = vec![];
+        for paragraph in &self.paragraphs {
+            for part_line in paragraph.split(".") {
+                if !part_line.is_empty() {
+                    let cleaned_line = part_line.trim().to_string();
+                    lines.push(cleaned_line);
+                }
+            }
+        }
+        lines
+    }
+}
+
+fn parse_selector(
+    selector: &str
+) -> Result<Selector, ScrapeError> {
+    Selector::parse(selector).map_err(|_e| ScrapeError::InvalidSelector(selector.to_string()))
+}
+
+fn first_text(
+    page: &Html,
+    selector: &str
+) -> Result<String, ScrapeError> {
+    Ok(page.select(&parse_selector(selector)?).next().map_or(String::new(), element_text))
+}
+
+// Text of an element with its whitespace collapsed
+fn element_text(
+    element: ElementRef
+) -> String {
+    element.text().collect::<String>().split_whitespace().collect::<Vec<_>>().join(" ")
+}
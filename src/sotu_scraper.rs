@@ -0,0 +1,388 @@
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use reqwest::Url;
+use select::document::Document;
+use select::predicate::Name;
+use scraper::Selector;
+
+use crate::CacheError;
+use crate::Corpus;
+use crate::FileError;
+use crate::HttpCache;
+use crate::SotuDocument;
+use crate::TextExtractor;
+use crate::downloader::Downloader;
+use crate::progress_log::ProgressLog;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ScrapeError {
+    // The request failed, e.g. the connection was refused or timed out
+    Network { url: String, message: String },
+    // The server answered with something other than success, e.g. 404
+    HttpStatus { url: String, status: u16 },
+    Cache(CacheError),
+    // The page has no element matching the content selector
+    MissingContent { url: String },
+    InvalidSelector(String),
+    // A setting that can't work, e.g. no concurrent downloads
+    InvalidConfig(String),
+    // The cache or the progress log couldn't be read or written
+    Io(FileError)
+}
+
+impl ScrapeError {
+    pub(crate) fn network(
+        url: &str,
+        error: &reqwest::Error
+    ) -> ScrapeError {
+        ScrapeError::Network { url: url.to_string(), message: error.to_string() }
+    }
+}
+
+impl fmt::Display for ScrapeError {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>
+    ) -> fmt::Result {
+        match self {
+            ScrapeError::Network { url, message } => {
+                write!(f, "Failed to download {}: {}", url, message)
+            }
+            ScrapeError::HttpStatus { url, status } => {
+                write!(f, "Server answered {} for {}", status, url)
+            }
+            ScrapeError::Cache(error) => {
+                write!(f, "{}", error)
+            }
+            ScrapeError::MissingContent { url } => {
+                write!(f, "No content found at: {}", url)
+            }
+            ScrapeError::InvalidSelector(selector) => {
+                write!(f, "Invalid selector supplied: {:?}", selector)
+            }
+            ScrapeError::InvalidConfig(message) => {
+                write!(f, "Invalid scraper config supplied: {}", message)
+            }
+            ScrapeError::Io(error) => {
+                write!(f, "{}", error)
+            }
+        }
+    }
+}
+
+impl Error for ScrapeError {}
+
+impl From<FileError> for ScrapeError {
+    fn from(
+        error: FileError
+    ) -> ScrapeError {
+        ScrapeError::Io(error)
+    }
+}
+
+// One of the site's lists of documents, e.g. the inaugural addresses
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Collection {
+    // Every document gathered from the collection is tagged with this, e.g. "inaugural"
+    pub name: String,
+    // Path of the page whose tables link to every document
+    pub directory_path: String,
+    // Only links starting with base_url followed by this path are followed
+    pub link_filter: String
+}
+
+impl Collection {
+    pub fn new(
+        name: &str,
+        directory_path: &str
+    ) -> Collection {
+        Collection {
+            name: name.to_string(),
+            directory_path: directory_path.to_string(),
+            link_filter: "/documents/".to_string()
+        }
+    }
+
+    pub fn state_of_the_union() -> Collection {
+        Collection::new(
+            "state-of-the-union",
+            "/documents/presidential-documents-archive-guidebook/annual-messages-congress-the-state-the-union"
+        )
+    }
+
+    pub fn inaugural_addresses() -> Collection {
+        Collection::new(
+            "inaugural",
+            "/documents/presidential-documents-archive-guidebook/inaugural-addresses"
+        )
+    }
+
+    pub fn farewell_addresses() -> Collection {
+        Collection::new(
+            "farewell",
+            "/documents/presidential-documents-archive-guidebook/farewell-addresses"
+        )
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScraperConfig {
+    // Scheme and host of the site, e.g. a local mirror's "http://127.0.0.1:8080"
+    pub base_url: String,
+    // Gathered in order. A document listed by more than one collection is gathered
+    // once, tagged with the first
+    pub collections: Vec<Collection>,
+    // CSS selectors for the element holding the text of an address, and for its metadata
+    pub content_selector: String,
+    pub president_selector: String,
+    pub title_selector: String,
+    pub date_selector: String,
+    // The links naming the site's categories for a document, which tell spoken addresses
+    // from written messages
+    pub category_selector: String,
+    // How the text of the content element is split into paragraphs and cleaned up
+    pub text_extractor: TextExtractor,
+    // Pages are read from the cache where possible, and downloaded pages are added to it.
+    // With an offline cache nothing is downloaded, and a page missing from it is an error
+    pub cache: Option<HttpCache>,
+    pub user_agent: String,
+    // Addresses downloaded at the same time
+    pub max_concurrent_downloads: usize,
+    // Politeness, requests to the server are at least this far apart
+    pub min_request_interval: Duration,
+    // Network errors, 429 and 5xx responses are retried this many times, waiting
+    // initial_backoff before the first retry and doubling the wait for each one after
+    pub max_retries: u32,
+    pub initial_backoff: Duration,
+    // Finished documents are recorded here, and not downloaded again when a scrape is
+    // restarted with the same log
+    pub progress_log: Option<PathBuf>
+}
+
+impl Default for ScraperConfig {
+    fn default() -> ScraperConfig {
+        ScraperConfig {
+            base_url: "https://www.presidency.ucsb.edu".to_string(),
+            collections: vec![Collection::state_of_the_union()],
+            content_selector: r#"div[class="field-docs-content"]"#.to_string(),
+            president_selector: ".diet-title".to_string(),
+            title_selector: ".field-ds-doc-title h1".to_string(),
+            date_selector: ".field-docs-start-date-time .date-display-single".to_string(),
+            category_selector: r#"a[href*="/documents/app-categories/"]"#.to_string(),
+            text_extractor: TextExtractor::default(),
+            cache: None,
+            user_agent: format!("grams/{} (State of the Union corpus scraper)", env!("CARGO_PKG_VERSION")),
+            max_concurrent_downloads: 4,
+            min_request_interval: Duration::from_millis(250),
+            max_retries: 3,
+            initial_backoff: Duration::from_secs(1),
+            progress_log: None
+        }
+    }
+}
+
+impl ScraperConfig {
+    pub fn directory_url(
+        &self,
+        collection: &Collection
+    ) -> String {
+        format!("{}{}", self.base_url, collection.directory_path)
+    }
+}
+
+pub struct SOTUScraper {
+    documents: Vec<SotuDocument>,
+    // Addresses that couldn't be gathered, the rest of the scrape carries on without them
+    failures: Vec<ScrapeError>,
+    text_lines: Vec<String>
+}
+
+impl SOTUScraper {
+    // Fails if the directory of addresses can't be read. Addresses that can't be
+    // downloaded or read are left out, and listed in failures()
+    pub fn gather_text() -> Result<SOTUScraper, ScrapeError> {
+        SOTUScraper::gather_text_with_config(&ScraperConfig::default())
+    }
+
+    pub fn gather_text_cached(
+        cache: &HttpCache
+    ) -> Result<SOTUScraper, ScrapeError> {
+        let config = ScraperConfig {
+            cache: Some(cache.clone()),
+            ..ScraperConfig::default()
+        };
+        SOTUScraper::gather_text_with_config(&config)
+    }
+
+    pub fn gather_text_with_config(
+        config: &ScraperConfig
+    ) -> Result<SOTUScraper, ScrapeError> {
+        // Check the selectors before downloading anything
+        for selector in [&config.content_selector, &config.president_selector, &config.title_selector, &config.date_selector, &config.category_selector] {
+            if Selector::parse(selector).is_err() {
+                return Err(ScrapeError::InvalidSelector(selector.to_string()));
+            }
+        }
+        if config.max_concurrent_downloads == 0 {
+            return Err(ScrapeError::InvalidConfig("max_concurrent_downloads must be at least 1".to_string()));
+        }
+
+        let downloader = Downloader::new(config)?;
+        let (progress_log, finished) = match &config.progress_log {
+            Some(path) => {
+                let (progress_log, finished) = ProgressLog::open(path)?;
+                (Some(progress_log), finished)
+            }
+            None => (None, HashMap::new())
+        };
+
+        // The links of every collection, with the collection they came from
+        let mut sotu_links: Vec<(&Collection, String)> = vec![];
+        let mut seen_links: HashSet<String> = HashSet::new();
+        for collection in &config.collections {
+            println!("Downloading {} links from {}.", collection.name, config.base_url);
+            for link in SOTUScraper::gather_links(&downloader, config, collection)? {
+                if seen_links.insert(link.to_string()) {
+                    sotu_links.push((collection, link));
+                }
+            }
+        }
+        let total_links = sotu_links.len();
+        println!("We have gathered {:?} links.", total_links);
+
+        // Each worker takes the next link nobody has started yet. Results are stored by
+        // position, so the documents come out in link order however the downloads finish
+        let next_link = AtomicUsize::new(0);
+        let results: Mutex<Vec<Option<Result<SotuDocument, ScrapeError>>>> = Mutex::new(vec![None; total_links]);
+        thread::scope(|scope| {
+            for _worker in 0..config.max_concurrent_downloads.min(total_links) {
+                scope.spawn(|| {
+                    loop {
+                        let pos = next_link.fetch_add(1, Ordering::SeqCst);
+                        let (collection, sotu_link) = match sotu_links.get(pos) {
+                            Some((collection, sotu_link)) => (collection, sotu_link),
+                            None => { break; }
+                        };
+
+                        let document = match finished.get(sotu_link) {
+                            Some(document) => {
+                                println!("{:?}/{total_links} -- Already finished: '{sotu_link}'", pos+1);
+                                // Logs written before collections were tagged have none
+                                Ok(SotuDocument { collection: collection.name.to_string(), ..document.clone() })
+                            }
+                            None => {
+                                println!("{:?}/{total_links} -- Downloading: '{sotu_link}'", pos+1);
+                                let document = downloader.get(sotu_link)
+                                    .and_then(|content| SotuDocument::from_html(sotu_link, &content, config))
+                                    .map(|document| SotuDocument { collection: collection.name.to_string(), ..document });
+                                match (&document, &progress_log) {
+                                    (Ok(document), Some(progress_log)) => progress_log.append(document).map(|_| document.clone()),
+                                    _ => document
+                                }
+                            }
+                        };
+                        results.lock().unwrap()[pos] = Some(document);
+                    }
+                });
+            }
+        });
+
+        // Goal: gather every address, with its metadata
+        let mut documents: Vec<SotuDocument> = vec![];
+        let mut failures: Vec<ScrapeError> = vec![];
+        for result in results.into_inner().unwrap().into_iter().flatten() {
+            match result {
+                Ok(document) => documents.push(document),
+                Err(e) => {
+                    println!("Skipped: {e}");
+                    failures.push(e);
+                }
+            }
+        }
+
+        let mut sotu_scraper = SOTUScraper::from_documents(documents);
+        sotu_scraper.failures = failures;
+        Ok(sotu_scraper)
+    }
+
+    // The links in the tables of a collection's directory page, sorted. Failing to read
+    // the directory fails the whole scrape
+    fn gather_links(
+        downloader: &Downloader,
+        config: &ScraperConfig,
+        collection: &Collection
+    ) -> Result<Vec<String>, ScrapeError> {
+        let directory_url = config.directory_url(collection);
+        let content = downloader.get(&directory_url)?;
+
+        let mut sotu_links: Vec<String> = vec![];
+        let link_prefix = format!("{}{}", config.base_url, collection.link_filter);
+
+        // Find start of tables
+        let mut table_contents: Vec<String> = vec![];
+
+        Document::from(content.as_str())
+            .find(Name("table"))
+            .for_each(|x| table_contents.push(x.html()));
+
+        // Relative links, as a mirror might have, are resolved against the directory page
+        let directory = Url::parse(&directory_url).ok();
+
+        // From every table in the document, gather the links to subpages
+        for individual_table in table_contents {
+            Document::from(individual_table.as_str())
+                .find(Name("a"))
+                .filter_map(|n| n.attr("href"))
+                .filter_map(|a| {
+                    match &directory {
+                        Some(directory) => directory.join(a).ok().map(|url| url.to_string()),
+                        None => Some(a.to_string())
+                    }
+                })
+                .filter(|a| a.starts_with(&link_prefix))
+                .for_each(|x| sotu_links.push(x));
+        }
+
+        sotu_links.sort_unstable();
+        sotu_links.dedup();
+        Ok(sotu_links)
+    }
+
+    pub fn from_documents(
+        documents: Vec<SotuDocument>
+    ) -> SOTUScraper {
+        let text_lines = documents.iter().flat_map(|document| document.lines()).collect();
+        SOTUScraper {documents, failures: vec![], text_lines}
+    }
+
+    pub fn failures(
+        &self
+    ) -> &[ScrapeError] {
+        &self.failures
+    }
+
+    pub fn documents(
+        &self
+    ) -> &[SotuDocument] {
+        &self.documents
+    }
+
+    pub fn corpus(
+        &self
+    ) -> Corpus {
+        Corpus::new(self.documents.to_vec())
+    }
+
+    pub fn get_line_iterator(
+        &mut self
+    ) -> std::slice::Iter<'_, String> {
+        self.text_lines.iter()
+    }
+}
@@ -0,0 +1,196 @@
+use rand::Rng;
+
+use crate::DecodingStrategy;
+use crate::GenerationConfig;
+use crate::NGramModel;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpeechConfig {
+    // The body stops at the end of the first sentence that reaches this many words
+    pub target_word_count: usize,
+    pub salutation: String,
+    pub closing: String,
+    // Sentences are cut off after this many words
+    pub max_sentence_size: usize
+}
+
+impl Default for SpeechConfig {
+    fn default() -> SpeechConfig {
+        SpeechConfig {
+            target_word_count: 500,
+            salutation: "Mr. Speaker, Madam Vice President, Members of Congress, and my fellow Americans:".to_string(),
+            closing: "Thank you. God bless you, and God bless the United States of America.".to_string(),
+            max_sentence_size: 25
+        }
+    }
+}
+
+// A paragraph is closed after this many sentences if the model hasn't ended it. Without
+// it a model cycling through sentences that never reach <P> or the end of a document,
+// e.g. "the cat sat. the cat sat.", would never finish a paragraph
+const MAX_SENTENCES_PER_PARAGRAPH: usize = 10;
+
+// When to stop generating paragraphs, whichever limit comes first
+struct BodyLimits {
+    max_paragraphs: Option<usize>,
+    target_word_count: Option<usize>,
+    max_sentence_size: usize,
+    max_sentences_per_paragraph: usize
+}
+
+impl NGramModel {
+    pub fn generate_paragraphs(
+        &self,
+        strategy: DecodingStrategy,
+        number_of_paragraphs: usize
+    ) -> Vec<String> {
+        self.generate_paragraphs_with_rng(strategy, number_of_paragraphs, &mut rand::thread_rng())
+    }
+
+    pub fn generate_paragraphs_with_rng<R: Rng>(
+        &self,
+        strategy: DecodingStrategy,
+        number_of_paragraphs: usize,
+        rng: &mut R
+    ) -> Vec<String> {
+        self.generate_paragraphs_with_config(strategy, number_of_paragraphs, &GenerationConfig::default(), rng)
+    }
+
+    // Paragraphs of sentences, carrying the history across sentence boundaries.
+    // The model should be trained with update_ngram_model_document. Sentences are cut
+    // off after the config's max_tokens words, its other settings aren't used
+    pub fn generate_paragraphs_with_config<R: Rng>(
+        &self,
+        strategy: DecodingStrategy,
+        number_of_paragraphs: usize,
+        config: &GenerationConfig,
+        rng: &mut R
+    ) -> Vec<String> {
+        NGramModel::validate_generation_arguments(&strategy, config);
+
+        let limits = BodyLimits {
+            max_paragraphs: Some(number_of_paragraphs),
+            target_word_count: None,
+            max_sentence_size: config.max_tokens,
+            max_sentences_per_paragraph: MAX_SENTENCES_PER_PARAGRAPH
+        };
+        self.generate_body(&strategy, &limits, rng)
+    }
+
+    pub fn generate_speech(
+        &self,
+        strategy: DecodingStrategy,
+        config: &SpeechConfig
+    ) -> String {
+        self.generate_speech_with_rng(strategy, config, &mut rand::thread_rng())
+    }
+
+    // A complete speech, the salutation, generated paragraphs of about the target
+    // word count and the closing, with paragraphs separated by blank lines
+    pub fn generate_speech_with_rng<R: Rng>(
+        &self,
+        strategy: DecodingStrategy,
+        config: &SpeechConfig,
+        rng: &mut R
+    ) -> String {
+        if let Err(e) = strategy.validate() {
+            panic!("Invalid decoding strategy supplied: {}", e);
+        }
+
+        let limits = BodyLimits {
+            max_paragraphs: None,
+            target_word_count: Some(config.target_word_count),
+            max_sentence_size: config.max_sentence_size,
+            max_sentences_per_paragraph: MAX_SENTENCES_PER_PARAGRAPH
+        };
+
+        let mut paragraphs: Vec<String> = vec![];
+        if !config.salutation.is_empty() {
+            paragraphs.push(config.salutation.to_string());
+        }
+        paragraphs.extend(self.generate_body(&strategy, &limits, rng));
+        if !config.closing.is_empty() {
+            paragraphs.push(config.closing.to_string());
+        }
+        paragraphs.join("\n\n")
+    }
+
+    fn generate_body<R: Rng>(
+        &self,
+        strategy: &DecodingStrategy,
+        limits: &BodyLimits,
+        rng: &mut R
+    ) -> Vec<String> {
+        let mut paragraphs: Vec<String> = vec![];
+        let mut current_paragraph: Vec<String> = vec![];
+        let mut current_sentence: Vec<String> = vec![];
+        let mut word_count = 0;
+        // Guards against models that never produce a sentence, e.g. an empty one
+        let mut words_since_restart = 0;
+
+        let mut history = self.context_from_history(&[]);
+        loop {
+            let reached_limit = limits.max_paragraphs.is_some_and(|max| paragraphs.len() >= max) ||
+                limits.target_word_count.is_some_and(|target| word_count >= target);
+            if reached_limit {
+                break;
+            }
+
+            let mut generated_gram = match strategy.select_word(&self.backoff_continuations(&history), rng) {
+                Some(word) => word,
+                None => { break; }
+            };
+            if generated_gram != self.end_of_sentence &&
+                generated_gram != self.paragraph_break &&
+                current_sentence.len() >= limits.max_sentence_size {
+                // Cut off a runaway sentence
+                generated_gram = self.end_of_sentence.to_string();
+            }
+
+            if generated_gram == self.end_of_sentence && current_sentence.is_empty() {
+                // An end token straight after another is the end of a training document,
+                // so start again from the beginning with a new paragraph
+                close_paragraph(&mut current_paragraph, &mut paragraphs);
+                if words_since_restart == 0 {
+                    break;
+                }
+                words_since_restart = 0;
+                history = self.context_from_history(&[]);
+                continue;
+            } else if generated_gram == self.end_of_sentence {
+                word_count += current_sentence.len();
+                current_paragraph.push(format!("{}.", current_sentence.join(" ")));
+                current_sentence.clear();
+                // Finish the paragraph here if this was the last sentence, or the paragraph is full
+                if limits.target_word_count.is_some_and(|target| word_count >= target) ||
+                    current_paragraph.len() >= limits.max_sentences_per_paragraph {
+                    close_paragraph(&mut current_paragraph, &mut paragraphs);
+                }
+            } else if generated_gram == self.paragraph_break {
+                close_paragraph(&mut current_paragraph, &mut paragraphs);
+            } else {
+                current_sentence.push(generated_gram.to_string());
+                words_since_restart += 1;
+            }
+
+            // Remove and rotate history, only if history is large enough
+            if self.degree > 1 {
+                history.remove(0);
+                history.push(generated_gram);
+            }
+        }
+
+        close_paragraph(&mut current_paragraph, &mut paragraphs);
+        paragraphs
+    }
+}
+
+fn close_paragraph(
+    current_paragraph: &mut Vec<String>,
+    paragraphs: &mut Vec<String>
+) {
+    if !current_paragraph.is_empty() {
+        paragraphs.push(current_paragraph.join(" "));
+        current_paragraph.clear();
+    }
+}
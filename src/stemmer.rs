@@ -0,0 +1,312 @@
+// Implementation of the Porter stemming algorithm for English.
+// M.F. Porter, 1980, An algorithm for suffix stripping, Program, 14(3) pp 130-137.
+// Follows the reference ANSI C version, operating on lowercase ASCII words.
+// Words containing anything other than ASCII letters are only lowercased.
+
+struct PorterStemmer {
+    // Like the C version the steps only move k, so positions up to the j set by an
+    // earlier ends() stay readable. Only set_to() rewrites the word
+    b: Vec<u8>,
+    // End of the current stem
+    k: usize,
+    // General offset into the word, set by ends()
+    j: usize
+}
+
+impl PorterStemmer {
+    // True if b[i] is a consonant
+    fn cons(
+        &self,
+        i: usize
+    ) -> bool {
+        match self.b[i] {
+            b'a' | b'e' | b'i' | b'o' | b'u' => { false}
+            b'y' => {
+                if i == 0 {
+                    true
+                } else {
+                    !self.cons(i - 1)
+                }
+            }
+            _ => { true}
+        }
+    }
+
+    // Measures the number of consonant sequences between 0 and j:
+    //  <c><v>       gives 0
+    //  <c>vc<v>     gives 1
+    //  <c>vcvc<v>   gives 2
+    fn m(
+        &self
+    ) -> usize {
+        let mut n = 0;
+        let mut i = 0;
+        loop {
+            if i > self.j { return n; }
+            if !self.cons(i) { break; }
+            i += 1;
+        }
+        i += 1;
+        loop {
+            loop {
+                if i > self.j { return n; }
+                if self.cons(i) { break; }
+                i += 1;
+            }
+            i += 1;
+            n += 1;
+            loop {
+                if i > self.j { return n; }
+                if !self.cons(i) { break; }
+                i += 1;
+            }
+            i += 1;
+        }
+    }
+
+    // True if 0..=j contains a vowel
+    fn vowel_in_stem(
+        &self
+    ) -> bool {
+        (0..=self.j).any(|i| !self.cons(i))
+    }
+
+    // True if j, (j - 1) contain a double consonant
+    fn double_c(
+        &self,
+        j: usize
+    ) -> bool {
+        if j < 1 || self.b[j] != self.b[j - 1] {
+            return false;
+        }
+        self.cons(j)
+    }
+
+    // True if i - 2, i - 1, i has the form consonant - vowel - consonant
+    // and the second consonant is not w, x or y. Used when restoring an 'e'
+    // at the end of a short word, e.g. cav(e), lov(e), hop(e), crim(e)
+    fn cvc(
+        &self,
+        i: usize
+    ) -> bool {
+        if i < 2 || !self.cons(i) || self.cons(i - 1) || !self.cons(i - 2) {
+            return false;
+        }
+        !matches!(self.b[i], b'w' | b'x' | b'y')
+    }
+
+    // True if 0..=k ends with the string s, setting j to the end of the stem
+    fn ends(
+        &mut self,
+        s: &str
+    ) -> bool {
+        let s = s.as_bytes();
+        let length = s.len();
+        if length > self.k + 1 {
+            return false;
+        }
+        if &self.b[self.k + 1 - length..=self.k] != s {
+            return false;
+        }
+        // j may wrap below zero for a whole-word match, m() then sees an empty stem
+        self.j = (self.k + 1 - length).wrapping_sub(1);
+        true
+    }
+
+    // Sets (j + 1)..=k to the string s, readjusting k
+    fn set_to(
+        &mut self,
+        s: &str
+    ) {
+        let start = self.j.wrapping_add(1);
+        self.b.truncate(start);
+        self.b.extend_from_slice(s.as_bytes());
+        self.k = self.b.len() - 1;
+    }
+
+    fn r(
+        &mut self,
+        s: &str
+    ) {
+        if self.j != usize::MAX && self.m() > 0 {
+            self.set_to(s);
+        }
+    }
+
+    // Gets rid of plurals and -ed or -ing, e.g.
+    //  caresses  ->  caress
+    //  ponies    ->  poni
+    //  feed      ->  feed
+    //  agreed    ->  agree
+    //  motoring  ->  motor
+    //  sing      ->  sing
+    fn step1ab(
+        &mut self
+    ) {
+        if self.b[self.k] == b's' {
+            if self.ends("sses") {
+                self.k -= 2;
+            } else if self.ends("ies") {
+                self.set_to("i");
+            } else if self.k >= 1 && self.b[self.k - 1] != b's' {
+                self.k -= 1;
+            }
+        }
+        if self.ends("eed") {
+            if self.j != usize::MAX && self.m() > 0 {
+                self.k -= 1;
+            }
+        } else if (self.ends("ed") || self.ends("ing")) && self.j != usize::MAX && self.vowel_in_stem() {
+            self.k = self.j;
+            if self.ends("at") {
+                self.set_to("ate");
+            } else if self.ends("bl") {
+                self.set_to("ble");
+            } else if self.ends("iz") {
+                self.set_to("ize");
+            } else if self.double_c(self.k) {
+                if !matches!(self.b[self.k], b'l' | b's' | b'z') {
+                    self.k -= 1;
+                }
+            } else {
+                self.j = self.k;
+                if self.m() == 1 && self.cvc(self.k) {
+                    self.set_to("e");
+                }
+            }
+        }
+    }
+
+    // Turns terminal y to i when there is another vowel in the stem
+    fn step1c(
+        &mut self
+    ) {
+        if self.ends("y") && self.j != usize::MAX && self.vowel_in_stem() {
+            self.b[self.k] = b'i';
+        }
+    }
+
+    // Maps double suffixes to single ones, e.g. -ization ( = -ize plus -ation)
+    // maps to -ize. The string before the suffix must give m() > 0
+    fn step2(
+        &mut self
+    ) {
+        if self.k < 1 {
+            return;
+        }
+        let rules: &[(&str, &str)] = match self.b[self.k - 1] {
+            b'a' => &[("ational", "ate"), ("tional", "tion")],
+            b'c' => &[("enci", "ence"), ("anci", "ance")],
+            b'e' => &[("izer", "ize")],
+            b'l' => &[("bli", "ble"), ("alli", "al"), ("entli", "ent"), ("eli", "e"), ("ousli", "ous")],
+            b'o' => &[("ization", "ize"), ("ation", "ate"), ("ator", "ate")],
+            b's' => &[("alism", "al"), ("iveness", "ive"), ("fulness", "ful"), ("ousness", "ous")],
+            b't' => &[("aliti", "al"), ("iviti", "ive"), ("biliti", "ble")],
+            b'g' => &[("logi", "log")],
+            _ => &[]
+        };
+        for (suffix, replacement) in rules {
+            if self.ends(suffix) {
+                self.r(replacement);
+                return;
+            }
+        }
+    }
+
+    // Deals with -ic-, -full, -ness etc.
+    fn step3(
+        &mut self
+    ) {
+        let rules: &[(&str, &str)] = match self.b[self.k] {
+            b'e' => &[("icate", "ic"), ("ative", ""), ("alize", "al")],
+            b'i' => &[("iciti", "ic")],
+            b'l' => &[("ical", "ic"), ("ful", "")],
+            b's' => &[("ness", "")],
+            _ => &[]
+        };
+        for (suffix, replacement) in rules {
+            if self.ends(suffix) {
+                self.r(replacement);
+                return;
+            }
+        }
+    }
+
+    // Takes off -ant, -ence etc., in context <c>vcvc<v>
+    fn step4(
+        &mut self
+    ) {
+        if self.k < 1 {
+            return;
+        }
+        let suffixes: &[&str] = match self.b[self.k - 1] {
+            b'a' => &["al"],
+            b'c' => &["ance", "ence"],
+            b'e' => &["er"],
+            b'i' => &["ic"],
+            b'l' => &["able", "ible"],
+            b'n' => &["ant", "ement", "ment", "ent"],
+            b'o' => &["ion", "ou"],
+            b's' => &["ism"],
+            b't' => &["ate", "iti"],
+            b'u' => &["ous"],
+            b'v' => &["ive"],
+            b'z' => &["ize"],
+            _ => &[]
+        };
+        for suffix in suffixes {
+            if self.ends(suffix) {
+                if *suffix == "ion" && (self.j == usize::MAX || !matches!(self.b[self.j], b's' | b't')) {
+                    return;
+                }
+                if self.j != usize::MAX && self.m() > 1 {
+                    self.k = self.j;
+                }
+                return;
+            }
+        }
+    }
+
+    // Removes a final -e if m() > 1, and changes -ll to -l if m() > 1
+    fn step5(
+        &mut self
+    ) {
+        self.j = self.k;
+        if self.b[self.k] == b'e' {
+            let a = self.m();
+            if a > 1 || (a == 1 && !self.cvc(self.k - 1)) {
+                self.k -= 1;
+            }
+        }
+        if self.b[self.k] == b'l' && self.double_c(self.k) && self.m() > 1 {
+            self.k -= 1;
+        }
+    }
+}
+
+pub fn porter_stem(
+    word: &str
+) -> String {
+    let lowered = word.to_lowercase();
+    // Strings of length 1 or 2 don't go through the stemming process
+    if lowered.len() <= 2 || !lowered.bytes().all(|c| c.is_ascii_lowercase()) {
+        return lowered;
+    }
+
+    let mut stemmer = PorterStemmer {
+        k: lowered.len() - 1,
+        b: lowered.into_bytes(),
+        j: 0
+    };
+    stemmer.step1ab();
+    if stemmer.k > 0 {
+        stemmer.step1c();
+        stemmer.step2();
+        stemmer.step3();
+        stemmer.step4();
+        stemmer.step5();
+    }
+    stemmer.b.truncate(stemmer.k + 1);
+
+    String::from_utf8(stemmer.b).unwrap()
+}
@@ -0,0 +1,122 @@
+use std::collections::HashSet;
+use std::io;
+use std::path::Path;
+
+use crate::read_lines;
+
+// Based on the NLTK English stop-word list, with apostrophes removed to match
+// the NGramModel tokeniser (which strips them, "don't" becomes "dont")
+const ENGLISH_STOP_WORDS: &[&str] = &[
+    "i", "me", "my", "myself", "we", "our", "ours", "ourselves", "you", "youre", "youve", "youll",
+    "youd", "your", "yours", "yourself", "yourselves", "he", "him", "his", "himself", "she", "shes",
+    "her", "hers", "herself", "it", "its", "itself", "they", "them", "their", "theirs", "themselves",
+    "what", "which", "who", "whom", "this", "that", "thatll", "these", "those", "am", "is", "are",
+    "was", "were", "be", "been", "being", "have", "has", "had", "having", "do", "does", "did",
+    "doing", "a", "an", "the", "and", "but", "if", "or", "because", "as", "until", "while", "of",
+    "at", "by", "for", "with", "about", "against", "between", "into", "through", "during", "before",
+    "after", "above", "below", "to", "from", "up", "down", "in", "out", "on", "off", "over", "under",
+    "again", "further", "then", "once", "here", "there", "when", "where", "why", "how", "all", "any",
+    "both", "each", "few", "more", "most", "other", "some", "such", "no", "nor", "not", "only", "own",
+    "same", "so", "than", "too", "very", "s", "t", "can", "will", "just", "don", "dont", "should",
+    "shouldve", "now", "d", "ll", "m", "o", "re", "ve", "y", "ain", "aren", "arent", "couldn",
+    "couldnt", "didn", "didnt", "doesn", "doesnt", "hadn", "hadnt", "hasn", "hasnt", "haven",
+    "havent", "isn", "isnt", "ma", "mightn", "mightnt", "mustn", "mustnt", "needn", "neednt", "shan",
+    "shant", "shouldn", "shouldnt", "wasn", "wasnt", "weren", "werent", "won", "wont", "wouldn",
+    "wouldnt"
+];
+
+#[derive(Clone, Debug, Default)]
+pub struct StopWords {
+    words: HashSet<String>
+}
+
+// How an ngram containing stop-words is excluded from a query
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StopWordExclusion {
+    // Exclude ngrams made up only of stop-words, e.g. "of the"
+    Entirely,
+    // Exclude ngrams containing any stop-word, e.g. "the people"
+    Partially
+}
+
+impl StopWords {
+    pub fn new() -> StopWords {
+        StopWords {
+            words: HashSet::new()
+        }
+    }
+
+    pub fn english() -> StopWords {
+        let mut stop_words = StopWords::new();
+        for word in ENGLISH_STOP_WORDS {
+            stop_words.insert(word);
+        }
+        stop_words
+    }
+
+    // Load a stop-word file, one or more words per line separated by whitespace.
+    // Empty lines and lines starting with '#' are skipped
+    pub fn from_file<P>(
+        filename: P
+    ) -> io::Result<StopWords>
+    where P: AsRef<Path>, {
+        let mut stop_words = StopWords::new();
+        stop_words.extend_from_file(filename)?;
+        Ok(stop_words)
+    }
+
+    pub fn extend_from_file<P>(
+        &mut self,
+        filename: P
+    ) -> io::Result<()>
+    where P: AsRef<Path>, {
+        for line in read_lines(filename)? {
+            let line = line?;
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            for word in trimmed.split_whitespace() {
+                self.insert(word);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn insert(
+        &mut self,
+        word: &str
+    ) {
+        self.words.insert(word.to_lowercase());
+    }
+
+    pub fn contains(
+        &self,
+        word: &str
+    ) -> bool {
+        self.words.contains(&word.to_lowercase())
+    }
+
+    pub fn len(
+        &self
+    ) -> usize {
+        self.words.len()
+    }
+
+    pub fn is_empty(
+        &self
+    ) -> bool {
+        self.words.is_empty()
+    }
+
+    pub fn excludes(
+        &self,
+        gram: &[String],
+        exclusion: StopWordExclusion
+    ) -> bool {
+        match exclusion {
+            StopWordExclusion::Entirely => { gram.iter().all(|word| self.contains(word))}
+            StopWordExclusion::Partially => { gram.iter().any(|word| self.contains(word))}
+        }
+    }
+}
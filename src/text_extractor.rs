@@ -0,0 +1,185 @@
+use scraper::{ElementRef, Node};
+
+// Elements that start a paragraph of their own
+const BLOCK_ELEMENTS: [&str; 24] = [
+    "address", "article", "blockquote", "center", "dd", "div", "dl", "dt", "h1", "h2", "h3", "h4",
+    "h5", "h6", "hr", "li", "ol", "p", "pre", "section", "table", "td", "tr", "ul"
+];
+
+const HEADING_ELEMENTS: [&str; 6] = ["h1", "h2", "h3", "h4", "h5", "h6"];
+
+// First words of the site's stage directions. Other brackets, such as an editor's "[sic]"
+// or a word filled into a quotation, are part of the text
+const STAGE_DIRECTIONS: [&str; 10] = [
+    "applause", "laughter", "cheers", "cheering", "boos", "booing", "inaudible", "crosstalk",
+    "music", "silence"
+];
+
+// What happens to editorial annotations, the site's bracketed notes of what happened
+// during a speech, e.g. "[Applause]" or "[Laughter]". Only brackets starting with one
+// of the extractor's stage directions are annotations
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Annotations {
+    #[default]
+    Drop,
+    // Left in the text as they are
+    Keep,
+    // Replaced by a token of their first word, "[Applause, the Members rising]" becomes "<applause>"
+    Tag
+}
+
+// Turns the content element of a page into paragraphs of plain text. Block elements such
+// as <p> and <div> are paragraphs of their own, a single <br> breaks a line within a
+// paragraph and two or more end it. Footnotes and headings aren't part of the speech,
+// so are left out unless kept
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextExtractor {
+    pub annotations: Annotations,
+    // Lowercase first words of the brackets that are annotations, e.g. "applause"
+    pub stage_directions: Vec<String>,
+    // Footnotes are <sup> reference marks, elements with "footnote" in their class or id,
+    // and the editor's closing "NOTE: The President spoke at..." paragraphs
+    pub keep_footnotes: bool,
+    pub keep_headings: bool
+}
+
+impl Default for TextExtractor {
+    fn default() -> TextExtractor {
+        TextExtractor {
+            annotations: Annotations::default(),
+            stage_directions: STAGE_DIRECTIONS.iter().map(|word| word.to_string()).collect(),
+            keep_footnotes: false,
+            keep_headings: false
+        }
+    }
+}
+
+impl TextExtractor {
+    // Paragraphs with their whitespace collapsed, lines within a paragraph separated by "\n"
+    pub fn extract(
+        &self,
+        content: ElementRef
+    ) -> Vec<String> {
+        let mut blocks: Vec<String> = vec![];
+        let mut block = String::new();
+        self.collect_blocks(content, &mut blocks, &mut block);
+        blocks.push(block);
+
+        let mut paragraphs: Vec<String> = vec![];
+        for block in blocks {
+            let block = self.apply_annotations(&block);
+            // A blank line, from a run of <br>, ends a paragraph
+            let mut lines: Vec<String> = vec![];
+            for line in block.split('\n').chain(std::iter::once("")) {
+                let line = line.split_whitespace().collect::<Vec<_>>().join(" ");
+                if !line.is_empty() {
+                    lines.push(line);
+                    continue;
+                }
+                if !lines.is_empty() {
+                    paragraphs.push(lines.join("\n"));
+                    lines.clear();
+                }
+            }
+        }
+        if !self.keep_footnotes {
+            paragraphs.retain(|paragraph| !paragraph.starts_with("NOTE:"));
+        }
+        paragraphs
+    }
+
+    // Gathers the text under the element into blocks, with "\n" for every <br>
+    fn collect_blocks(
+        &self,
+        element: ElementRef,
+        blocks: &mut Vec<String>,
+        block: &mut String
+    ) {
+        for child in element.children() {
+            if let Node::Text(text) = child.value() {
+                // Line breaks in the page source are just whitespace
+                block.push_str(&text.replace(&['\n', '\r'][..], " "));
+                continue;
+            }
+            let child = match ElementRef::wrap(child) {
+                Some(child) => child,
+                None => { continue; }
+            };
+            let name = child.value().name();
+            if name == "br" {
+                block.push('\n');
+                continue;
+            }
+            if self.is_skipped(child) {
+                continue;
+            }
+            let is_block = BLOCK_ELEMENTS.contains(&name);
+            if is_block {
+                blocks.push(std::mem::take(block));
+            }
+            self.collect_blocks(child, blocks, block);
+            if is_block {
+                blocks.push(std::mem::take(block));
+            }
+        }
+    }
+
+    fn is_skipped(
+        &self,
+        element: ElementRef
+    ) -> bool {
+        let name = element.value().name();
+        if name == "script" || name == "style" {
+            return true;
+        }
+        if !self.keep_headings && HEADING_ELEMENTS.contains(&name) {
+            return true;
+        }
+        if !self.keep_footnotes {
+            let is_footnote = name == "sup"
+                || element.value().classes().any(|class| class.to_lowercase().contains("footnote"))
+                || element.value().id().is_some_and(|id| id.to_lowercase().contains("footnote"));
+            if is_footnote {
+                return true;
+            }
+        }
+        false
+    }
+
+    fn apply_annotations(
+        &self,
+        text: &str
+    ) -> String {
+        if self.annotations == Annotations::Keep {
+            return text.to_string();
+        }
+        let mut result = String::new();
+        let mut rest = text;
+        while let Some(start) = rest.find('[') {
+            let end = match rest[start..].find(']') {
+                Some(length) => start + length,
+                // An unclosed bracket isn't an annotation
+                None => { break; }
+            };
+            let first_word = rest[start + 1..end]
+                .split(|c: char| !c.is_alphanumeric())
+                .find(|word| !word.is_empty())
+                .map(|word| word.to_lowercase());
+            let stage_direction = match first_word {
+                Some(word) if self.stage_directions.contains(&word) => word,
+                _ => {
+                    result.push_str(&rest[..=end]);
+                    rest = &rest[end + 1..];
+                    continue;
+                }
+            };
+            result.push_str(&rest[..start]);
+            if self.annotations == Annotations::Tag {
+                result.push_str(&format!("<{}>", stage_direction));
+            }
+            rest = &rest[end + 1..];
+        }
+        result.push_str(rest);
+        result
+    }
+}
@@ -0,0 +1,147 @@
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+
+use crate::read_lines;
+use crate::stemmer::porter_stem;
+
+// Built-in English forms, (form, lemma). Only forms with one lemma whatever their part
+// of speech, so no "building" (noun or verb), "left" (adjective or verb), "states"
+// ("United States") or comparatives like "better"
+const ENGLISH_LEMMAS: &[(&str, &str)] = &[
+    ("am", "be"), ("is", "be"), ("are", "be"), ("was", "be"), ("were", "be"), ("been", "be"),
+    ("has", "have"), ("had", "have"), ("having", "have"),
+    ("does", "do"), ("did", "do"), ("done", "do"), ("doing", "do"),
+    ("goes", "go"), ("went", "go"), ("gone", "go"), ("going", "go"),
+    ("made", "make"), ("makes", "make"), ("making", "make"),
+    ("said", "say"), ("says", "say"),
+    ("took", "take"), ("taken", "take"), ("takes", "take"), ("taking", "take"),
+    ("gave", "give"), ("given", "give"), ("gives", "give"), ("giving", "give"),
+    ("came", "come"), ("comes", "come"), ("coming", "come"),
+    ("seen", "see"), ("sees", "see"), ("seeing", "see"),
+    ("knew", "know"), ("known", "know"), ("knows", "know"), ("knowing", "know"),
+    ("thinks", "think"),
+    ("brought", "bring"), ("brings", "bring"), ("bringing", "bring"),
+    ("built", "build"), ("builds", "build"),
+    ("paid", "pay"), ("pays", "pay"), ("paying", "pay"),
+    ("spent", "spend"), ("spends", "spend"),
+    ("won", "win"), ("wins", "win"),
+    ("fought", "fight"), ("fights", "fight"), ("fighting", "fight"),
+    ("led", "lead"), ("leads", "lead"),
+    ("met", "meet"), ("meets", "meet"),
+    ("kept", "keep"), ("keeps", "keep"), ("keeping", "keep"),
+    ("began", "begin"), ("begun", "begin"), ("begins", "begin"),
+    ("stood", "stand"), ("stands", "stand"),
+    ("sought", "seek"), ("seeks", "seek"), ("seeking", "seek"),
+    ("taxes", "tax"), ("taxed", "tax"),
+    ("men", "man"), ("women", "woman"), ("children", "child"),
+    ("wives", "wife"), ("feet", "foot"), ("teeth", "tooth"),
+    ("nations", "nation"), ("years", "year"), ("americans", "american"),
+    ("laws", "law"), ("jobs", "job"), ("families", "family"), ("workers", "worker")
+];
+
+#[derive(Clone, Debug, Default)]
+pub struct Lemmatiser {
+    lemmas: HashMap<String, String>
+}
+
+impl Lemmatiser {
+    pub fn new() -> Lemmatiser {
+        Lemmatiser {
+            lemmas: HashMap::new()
+        }
+    }
+
+    pub fn english() -> Lemmatiser {
+        let mut lemmatiser = Lemmatiser::new();
+        for (form, lemma) in ENGLISH_LEMMAS {
+            lemmatiser.insert(form, lemma);
+        }
+        lemmatiser
+    }
+
+    // Load a dictionary file, one "form lemma" pair per line, separated by whitespace.
+    // Empty lines and lines starting with '#' are skipped
+    pub fn from_file<P>(
+        filename: P
+    ) -> io::Result<Lemmatiser>
+    where P: AsRef<Path>, {
+        let mut lemmatiser = Lemmatiser::new();
+        lemmatiser.extend_from_file(filename)?;
+        Ok(lemmatiser)
+    }
+
+    pub fn extend_from_file<P>(
+        &mut self,
+        filename: P
+    ) -> io::Result<()>
+    where P: AsRef<Path>, {
+        for line in read_lines(filename)? {
+            let line = line?;
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            let parts: Vec<&str> = trimmed.split_whitespace().collect();
+            if parts.len() != 2 {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Expected 'form lemma' in lemma dictionary, got: {:?}", trimmed)
+                ));
+            }
+            self.insert(parts[0], parts[1]);
+        }
+        Ok(())
+    }
+
+    pub fn insert(
+        &mut self,
+        form: &str,
+        lemma: &str
+    ) {
+        self.lemmas.insert(form.to_lowercase(), lemma.to_lowercase());
+    }
+
+    // Words missing from the dictionary are returned unchanged
+    pub fn lemmatise(
+        &self,
+        word: &str
+    ) -> String {
+        match self.lemmas.get(&word.to_lowercase()) {
+            Some(lemma) => { lemma.to_string()}
+            None => { word.to_string()}
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum TokenFilter {
+    Lowercase,
+    PorterStemmer,
+    Lemmatiser(Lemmatiser)
+}
+
+impl TokenFilter {
+    pub fn apply(
+        &self,
+        token: &str
+    ) -> String {
+        match self {
+            TokenFilter::Lowercase => { token.to_lowercase()}
+            TokenFilter::PorterStemmer => { porter_stem(token)}
+            TokenFilter::Lemmatiser(lemmatiser) => { lemmatiser.lemmatise(token)}
+        }
+    }
+}
+
+// Run a token through each filter in turn
+pub fn apply_token_filters(
+    filters: &[TokenFilter],
+    token: &str
+) -> String {
+    let mut filtered = token.to_string();
+    for filter in filters {
+        filtered = filter.apply(&filtered);
+    }
+    filtered
+}
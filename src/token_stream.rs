@@ -0,0 +1,142 @@
+use rand::Rng;
+use rand::rngs::ThreadRng;
+
+use crate::DecodingStrategy;
+use crate::GenerationConfig;
+use crate::NGramModel;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GeneratedToken {
+    pub token: String,
+    // The probability the token was chosen with, after the config removed any forbidden words
+    pub probability: f64,
+    // The (degree - 1) words of context before the token. When that context wasn't seen in
+    // training, or the config forbade everything that followed it, the model backed off and
+    // the token was predicted from a shorter tail of it, down to no words at all
+    pub history: Vec<String>
+}
+
+// Generates a sentence one token at a time, so output can be shown as it is produced
+// and the caller can stop early. Ends at the end of sentence token, which isn't
+// yielded, a stop phrase or the config's max_tokens
+pub struct TokenStream<'a, R: Rng> {
+    model: &'a NGramModel,
+    strategy: DecodingStrategy,
+    config: GenerationConfig,
+    rng: R,
+    history: Vec<String>,
+    stop_phrases: Vec<Vec<String>>,
+    generated: Vec<String>,
+    finished: bool
+}
+
+impl<'a, R: Rng> TokenStream<'a, R> {
+    pub(crate) fn new(
+        model: &'a NGramModel,
+        history: Vec<String>,
+        strategy: DecodingStrategy,
+        config: GenerationConfig,
+        rng: R
+    ) -> TokenStream<'a, R> {
+        NGramModel::validate_generation_arguments(&strategy, &config);
+
+        // Stop phrases are tokenised like the training text, so they can match generated words
+        let stop_phrases: Vec<Vec<String>> = config.stop_phrases
+            .iter()
+            .map(|phrase| model.string_to_string_vec(phrase.to_string()))
+            .filter(|phrase| !phrase.is_empty())
+            .collect();
+
+        TokenStream {
+            model,
+            strategy,
+            config,
+            rng,
+            history,
+            stop_phrases,
+            generated: vec![],
+            finished: false
+        }
+    }
+
+    // Every token yielded so far
+    pub fn generated(
+        &self
+    ) -> &[String] {
+        &self.generated
+    }
+}
+
+impl<'a, R: Rng> Iterator for TokenStream<'a, R> {
+    type Item = GeneratedToken;
+
+    fn next(
+        &mut self
+    ) -> Option<GeneratedToken> {
+        if self.finished || self.generated.len() >= self.config.max_tokens {
+            return None;
+        }
+
+        // Running out of allowed words ends the sentence
+        let continuations = self.model.allowed_continuations(&self.history, &self.generated, &self.config);
+        let generated_gram = match self.strategy.select_word(&continuations, &mut self.rng) {
+            Some(word) if word != self.model.end_of_sentence => word,
+            _ => {
+                self.finished = true;
+                return None;
+            }
+        };
+        let probability = continuations
+            .iter()
+            .find(|(word, _probability)| *word == generated_gram)
+            .map_or(0.0, |(_word, probability)| *probability);
+        let token = GeneratedToken {
+            token: generated_gram.to_string(),
+            probability,
+            history: self.history.clone()
+        };
+
+        // Remove and rotate history, only if history is large enough
+        if self.model.degree > 1 {
+            self.history.remove(0);
+            self.history.push(generated_gram.to_string());
+        }
+        self.generated.push(generated_gram);
+
+        if self.stop_phrases.iter().any(|phrase| self.generated.ends_with(phrase)) {
+            self.finished = true;
+        }
+        Some(token)
+    }
+}
+
+impl NGramModel {
+    pub fn stream_tokens(
+        &self,
+        strategy: DecodingStrategy
+    ) -> TokenStream<'_, ThreadRng> {
+        self.stream_tokens_with_rng(strategy, GenerationConfig::default(), rand::thread_rng())
+    }
+
+    // A new sentence, one token at a time. The rng can be a &mut to one the caller keeps
+    pub fn stream_tokens_with_rng<R: Rng>(
+        &self,
+        strategy: DecodingStrategy,
+        config: GenerationConfig,
+        rng: R
+    ) -> TokenStream<'_, R> {
+        TokenStream::new(self, self.context_from_history(&[]), strategy, config, rng)
+    }
+
+    // The continuation of the prompt, one token at a time. The prompt itself isn't yielded
+    pub fn stream_from_prompt_with_rng<R: Rng>(
+        &self,
+        prompt: &str,
+        strategy: DecodingStrategy,
+        config: GenerationConfig,
+        rng: R
+    ) -> TokenStream<'_, R> {
+        let words = self.string_to_string_vec(prompt.to_string());
+        TokenStream::new(self, self.context_from_history(&words), strategy, config, rng)
+    }
+}
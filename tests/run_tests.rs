@@ -1,6 +1,30 @@
 mod tests {
     use grams::NGramModel;
+    use grams::DecodingStrategy;
+    use grams::BeamSearchConfig;
+    use grams::BidirectionalModel;
+    use grams::{ConstraintError, LexicalConstraints};
+    use grams::GenerationConfig;
+    use grams::SpeechConfig;
+    use grams::GeneratedToken;
+    use grams::{CopiedSpan, NoveltyConfig};
+    use rand::{Rng, SeedableRng};
+    use rand::rngs::StdRng;
     use grams::read_lines;
+    use grams::{Lemmatiser, TokenFilter};
+    use grams::{StopWordExclusion, StopWords};
+    use grams::NGramFilter;
+    use grams::{NGramPattern, QueryError, QueryRanking};
+    use grams::stemmer::porter_stem;
+    use grams::{CacheError, Collection, HttpCache, SOTUScraper, ScrapeError, ScraperConfig, SotuDocument};
+    use grams::{Corpus, CorpusFilter, DeliveryType, Party};
+    use grams::{CorpusError, CorpusSource, TextDirectorySource, TextFileSource};
+    use grams::{JsonlSource, TextArchiveSource};
+    use grams::{Annotations, TextExtractor};
+    use std::sync::{Arc, Mutex};
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
 
     #[test]
     fn generate_text_trigram_biden_2024() {
@@ -9,14 +33,14 @@ mod tests {
         // File text_sample.txt must exist in the current path
         if let Ok(lines) = read_lines("text_samples/biden_sotu_2024.txt") {
             // Use lines from the iterator
-            for line in lines.flatten() {
+            for line in lines.map_while(Result::ok) {
                 if !line.is_empty() {
                     bmodel.update_ngram_model(line);
                 }
             }
         }
 
-        let got = bmodel.generate_text("Greedy".to_string(), 1).first().unwrap().to_string();
+        let got = bmodel.generate_text(DecodingStrategy::Greedy, 1).first().unwrap().to_string();
         
         let want = "I see a future where we all come from somewhere but we are".to_string();
         assert_eq!(got, want);
@@ -29,14 +53,14 @@ mod tests {
         // File text_sample.txt must exist in the current path
         if let Ok(lines) = read_lines("text_samples/biden_sotu_2022.txt") {
             // Use lines from the iterator
-            for line in lines.flatten() {
+            for line in lines.map_while(Result::ok) {
                 if !line.is_empty() {
                     bmodel.update_ngram_model(line);
                 }
             }
         }
 
-        let got = bmodel.generate_text("Greedy".to_string(), 1).first().unwrap().to_string();
+        let got = bmodel.generate_text(DecodingStrategy::Greedy, 1).first().unwrap().to_string();
         
         let want = "And we will as one people".to_string();
         assert_eq!(got, want);
@@ -49,14 +73,14 @@ mod tests {
         // File text_sample.txt must exist in the current path
         if let Ok(lines) = read_lines("text_samples/biden_sotu_2022.txt") {
             // Use lines from the iterator
-            for line in lines.flatten() {
+            for line in lines.map_while(Result::ok) {
                 if !line.is_empty() {
                     bmodel.update_ngram_model(line);
                 }
             }
         }
 
-        let got = bmodel.generate_text("Greedy".to_string(), 1).first().unwrap().to_string();
+        let got = bmodel.generate_text(DecodingStrategy::Greedy, 1).first().unwrap().to_string();
         
         // This will be the most probable word 25 times, the limit before max sentence
         let want = "the the the the the the the the the the the the the the the the the the the the the the the the the".to_string();
@@ -116,7 +140,7 @@ mod tests {
 
         let partial_ngram = vec!["again".to_string()];
         let got = bmodel.probability_for_partial_ngram(&partial_ngram);
-        let want = 2 as f64 / 10 as f64;
+        let want = 2.0 / 10.0;
         assert_eq!(got, want);
     }
 
@@ -134,17 +158,17 @@ mod tests {
 
         let partial_ngram = vec!["mango".to_string(), "test".to_string()];
         let got = bmodel.probability_for_partial_ngram(&partial_ngram);
-        let want = 1 as f64 / 4 as f64;
+        let want = 1.0 / 4.0;
         assert_eq!(got, want);
 
         let partial_ngram = vec!["cake".to_string(), "test".to_string()];
         let got = bmodel.probability_for_partial_ngram(&partial_ngram);
-        let want = 2 as f64 / 4 as f64;
+        let want = 2.0 / 4.0;
         assert_eq!(got, want);
 
         let partial_ngram = vec!["mango".to_string(), "cake".to_string()];
         let got = bmodel.probability_for_partial_ngram(&partial_ngram);
-        let want = 3 as f64 / 4 as f64;
+        let want = 3.0 / 4.0;
         assert_eq!(got, want);
     }
 
@@ -364,7 +388,7 @@ mod tests {
             &bmodel.last_given_penultimate_counts,
             &sample_bigram
         );
-        let want = 1 as f64 / 2 as f64;
+        let want = 1.0 / 2.0;
         assert_eq!(got, want);
     }
 
@@ -400,7 +424,7 @@ mod tests {
             &bmodel.last_given_penultimate_counts,
             &sample_bigram
         );
-        let want = 1 as f64 / 4 as f64;
+        let want = 1.0 / 4.0;
         assert_eq!(got, want);
     }
 
@@ -427,7 +451,7 @@ mod tests {
         // File text_sample.txt must exist in the current path
         if let Ok(lines) = read_lines("text_samples/biden_sotu_2022.txt") {
             // Use lines from the iterator
-            for line in lines.flatten() {
+            for line in lines.map_while(Result::ok) {
                 if !line.is_empty() {
                     bmodel.update_ngram_model(line);
                 }
@@ -450,7 +474,7 @@ mod tests {
         // File text_sample.txt must exist in the current path
         if let Ok(lines) = read_lines("text_samples/biden_sotu_2022.txt") {
             // Use lines from the iterator
-            for line in lines.flatten() {
+            for line in lines.map_while(Result::ok) {
                 if !line.is_empty() {
                     bmodel.update_ngram_model(line);
                 }
@@ -473,7 +497,7 @@ mod tests {
         // File text_sample.txt must exist in the current path
         if let Ok(lines) = read_lines("text_samples/biden_sotu_2022.txt") {
             // Use lines from the iterator
-            for line in lines.flatten() {
+            for line in lines.map_while(Result::ok) {
                 if !line.is_empty() {
                     bmodel.update_ngram_model(line);
                 }
@@ -499,7 +523,7 @@ mod tests {
         // File text_sample.txt must exist in the current path
         if let Ok(lines) = read_lines("text_samples/biden_sotu_2022.txt") {
             // Use lines from the iterator
-            for line in lines.flatten() {
+            for line in lines.map_while(Result::ok) {
                 if !line.is_empty() {
                     bmodel.update_ngram_model(line);
                 }
@@ -517,4 +541,1789 @@ mod tests {
 
         assert_eq!(got, want);
     }
+
+    #[test]
+    fn porter_stemmer_known_words() {
+        let words = vec![
+            ("caresses", "caress"), ("ponies", "poni"), ("cats", "cat"), ("feed", "feed"),
+            ("agreed", "agre"), ("plastered", "plaster"), ("motoring", "motor"), ("sing", "sing"),
+            ("conflated", "conflat"), ("hopping", "hop"), ("falling", "fall"), ("filing", "file"),
+            ("happy", "happi"), ("relational", "relat"), ("generalization", "gener"),
+            ("hopefulness", "hope"), ("electricity", "electr"), ("adjustment", "adjust"),
+            ("controlling", "control"), ("taxes", "tax"), ("taxed", "tax"), ("taxing", "tax"),
+            ("Nation", "nation"), ("is", "is")
+        ];
+        for (word, want) in words {
+            assert_eq!(porter_stem(word), want, "stemming {:?}", word);
+        }
+    }
+
+    #[test]
+    fn porter_stemmer_lle_endings() {
+        // Removing the final e used to leave m() reading past the end of the word
+        let words = vec![("michelle", "michel"), ("gazelle", "gazel"), ("belle", "bell"), ("alle", "all")];
+        for (word, want) in words {
+            assert_eq!(porter_stem(word), want, "stemming {:?}", word);
+        }
+
+        let mut rng = StdRng::seed_from_u64(26);
+        for _ in 0..20000 {
+            let length = rng.gen_range(1..12);
+            let word: String = (0..length).map(|_| rng.gen_range(b'a'..=b'z') as char).collect();
+            let stem = porter_stem(&word);
+            assert!(!stem.is_empty() && stem.len() <= word.len(), "stemming {:?}", word);
+        }
+    }
+
+    #[test]
+    fn lemmatiser_english_and_custom_entries() {
+        let mut lemmatiser = Lemmatiser::english();
+        assert_eq!(lemmatiser.lemmatise("Were"), "be");
+        assert_eq!(lemmatiser.lemmatise("children"), "child");
+        assert_eq!(lemmatiser.lemmatise("Congress"), "Congress");
+        // Forms whose lemma depends on how they're used are left alone
+        assert_eq!(lemmatiser.lemmatise("States"), "States");
+        assert_eq!(lemmatiser.lemmatise("building"), "building");
+        assert_eq!(lemmatiser.lemmatise("better"), "better");
+
+        lemmatiser.insert("Congresses", "congress");
+        assert_eq!(lemmatiser.lemmatise("congresses"), "congress");
+    }
+
+    #[test]
+    fn lemmatiser_from_file() {
+        let path = std::env::temp_dir().join("grams_lemmatiser_from_file.txt");
+        std::fs::write(&path, "# form lemma\nmice mouse\n\ngeese goose\n").unwrap();
+
+        let lemmatiser = Lemmatiser::from_file(&path).unwrap();
+        assert_eq!(lemmatiser.lemmatise("Mice"), "mouse");
+        assert_eq!(lemmatiser.lemmatise("geese"), "goose");
+        assert_eq!(lemmatiser.lemmatise("was"), "was");
+
+        std::fs::write(&path, "mice\n").unwrap();
+        assert!(Lemmatiser::from_file(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn stemmer_token_filter_collapses_counts() {
+        let mut bmodel = NGramModel::new(2);
+        bmodel.add_token_filter(TokenFilter::PorterStemmer);
+
+        bmodel.update_ngram_model("We cut taxes".to_string());
+        bmodel.update_ngram_model("They taxed us".to_string());
+        bmodel.update_ngram_model("Taxing work".to_string());
+
+        let unigram = vec!["tax".to_string()];
+        let got = bmodel.probability_for_partial_ngram(&unigram);
+        let want = 3.0 / 11.0;
+        assert_eq!(got, want);
+
+        assert!(bmodel.ngram_counts.contains_key(&vec!["cut".to_string(), "tax".to_string()]));
+        assert!(bmodel.ngram_counts.contains_key(&vec!["<S>".to_string(), "tax".to_string()]));
+    }
+
+    #[test]
+    fn most_common_ngram_with_token_filters() {
+        let mut bmodel = NGramModel::new(2);
+
+        bmodel.update_ngram_model("raise taxes".to_string());
+        bmodel.update_ngram_model("raised taxes".to_string());
+        bmodel.update_ngram_model("raising taxes".to_string());
+        bmodel.update_ngram_model("cut spending".to_string());
+        bmodel.update_ngram_model("cut spending".to_string());
+
+        let got = bmodel.most_common_ngram_without_sentence_tokens();
+        let want_bigram = vec!["cut".to_string(), "spending".to_string()];
+        assert_eq!(*got.unwrap().0, want_bigram);
+
+        let got = bmodel.most_common_ngram_with_token_filters(&[TokenFilter::PorterStemmer]).unwrap();
+        // Ties are broken alphabetically, and "<S>" sorts before any word
+        let want = (vec!["<S>".to_string(), "rais".to_string()], 3);
+        assert_eq!(got, want);
+
+        let lemmatiser = TokenFilter::Lemmatiser(Lemmatiser::english());
+        let got = bmodel.most_common_ngram_with_token_filters(&[lemmatiser]).unwrap();
+        let want = (vec!["tax".to_string(), "</S>".to_string()], 3);
+        assert_eq!(got, want);
+    }
+
+    #[test]
+    fn stop_words_english_and_from_file() {
+        let mut stop_words = StopWords::english();
+        assert!(stop_words.contains("The"));
+        assert!(stop_words.contains("dont"));
+        assert!(!stop_words.contains("America"));
+
+        let path = std::env::temp_dir().join("grams_stop_words_from_file.txt");
+        std::fs::write(&path, "# Extra stop words\nAmerica applause\n\nlaughter\n").unwrap();
+        stop_words.extend_from_file(&path).unwrap();
+        assert!(stop_words.contains("america"));
+        assert!(stop_words.contains("Laughter"));
+
+        let custom = StopWords::from_file(&path).unwrap();
+        assert_eq!(custom.len(), 3);
+        assert!(!custom.contains("the"));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn stop_words_exclusion_modes() {
+        let stop_words = StopWords::english();
+        let of_the = vec!["of".to_string(), "the".to_string()];
+        let the_people = vec!["the".to_string(), "people".to_string()];
+        let middle_class = vec!["middle".to_string(), "class".to_string()];
+
+        assert!(stop_words.excludes(&of_the, StopWordExclusion::Entirely));
+        assert!(!stop_words.excludes(&the_people, StopWordExclusion::Entirely));
+        assert!(stop_words.excludes(&the_people, StopWordExclusion::Partially));
+        assert!(!stop_words.excludes(&middle_class, StopWordExclusion::Partially));
+    }
+
+    #[test]
+    fn biden_2022_most_common_excluding_stop_words() {
+        let mut bmodel = NGramModel::new(2);
+
+        // File text_sample.txt must exist in the current path
+        if let Ok(lines) = read_lines("text_samples/biden_sotu_2022.txt") {
+            // Use lines from the iterator
+            for line in lines.map_while(Result::ok) {
+                if !line.is_empty() {
+                    bmodel.update_ngram_model(line);
+                }
+            }
+        }
+
+        let stop_words = StopWords::english();
+
+        // "of the" is the most common bigram overall, but is made up entirely of stop-words
+        let got = bmodel.most_common_ngram_excluding_stop_words(&stop_words, StopWordExclusion::Entirely).unwrap();
+        let want_bigram = vec!["the".to_string(), "world".to_string()];
+        assert_eq!(*got.0, want_bigram);
+        assert_eq!(*got.1, 18);
+
+        let got = bmodel.most_common_ngram_excluding_stop_words(&stop_words, StopWordExclusion::Partially).unwrap();
+        let want_bigram = vec!["United".to_string(), "States".to_string()];
+        assert_eq!(*got.0, want_bigram);
+        assert_eq!(*got.1, 8);
+    }
+
+    #[test]
+    fn collocation_report_pmi() {
+        let mut bmodel = NGramModel::new(2);
+
+        bmodel.update_ngram_model("the middle class".to_string());
+        bmodel.update_ngram_model("the middle class".to_string());
+        bmodel.update_ngram_model("the class of the people".to_string());
+
+        // P(middle class) / P(middle)P(class) = (2 / 14) / ((2 / 14) * (3 / 14))
+        let got = bmodel.collocation_report(2, None);
+        let want_ngrams = vec![
+            vec!["middle".to_string(), "class".to_string()],
+            vec!["the".to_string(), "middle".to_string()]
+        ];
+        assert_eq!(got.iter().map(|c| c.ngram.to_vec()).collect::<Vec<Vec<String>>>(), want_ngrams);
+        assert_eq!(got[0].count, 2);
+        assert_eq!(got[0].pmi, (14.0_f64 / 3.0).log2());
+
+        let stop_words = StopWords::english();
+        let got = bmodel.collocation_report(1, Some((&stop_words, StopWordExclusion::Partially)));
+        assert_eq!(got.len(), 1);
+        assert_eq!(got[0].ngram, vec!["middle".to_string(), "class".to_string()]);
+    }
+
+    #[test]
+    fn top_ngrams_ranked_with_ties() {
+        let mut bmodel = NGramModel::new(2);
+
+        let line_of_text = "mango test test mango cake mango monkey test mango".to_string();
+        bmodel.update_ngram_model(line_of_text);
+
+        let got = bmodel.top_ngrams(3, &NGramFilter::new().without_sentence_tokens());
+        assert_eq!(got.len(), 3);
+
+        let want_bigram = vec!["test".to_string(), "mango".to_string()];
+        assert_eq!(got[0].ngram, want_bigram);
+        assert_eq!(got[0].count, 2);
+        assert_eq!(got[0].relative_frequency, 2.0 / 10.0);
+
+        // Ties on a count of 1 are broken alphabetically
+        let want_bigram = vec!["cake".to_string(), "mango".to_string()];
+        assert_eq!(got[1].ngram, want_bigram);
+        let want_bigram = vec!["mango".to_string(), "cake".to_string()];
+        assert_eq!(got[2].ngram, want_bigram);
+        assert_eq!(got[2].relative_frequency, 1.0 / 10.0);
+    }
+
+    #[test]
+    fn biden_2022_top_ngrams_filters() {
+        let mut bmodel = NGramModel::new(2);
+
+        // File text_sample.txt must exist in the current path
+        if let Ok(lines) = read_lines("text_samples/biden_sotu_2022.txt") {
+            // Use lines from the iterator
+            for line in lines.map_while(Result::ok) {
+                if !line.is_empty() {
+                    bmodel.update_ngram_model(line);
+                }
+            }
+        }
+
+        let got = bmodel.top_ngrams(3, &NGramFilter::new());
+        let want = vec![
+            (vec!["<S>".to_string(), "And".to_string()], 43),
+            (vec!["<S>".to_string(), "We".to_string()], 30),
+            (vec!["of".to_string(), "the".to_string()], 27)
+        ];
+        assert_eq!(got.iter().map(|r| (r.ngram.to_vec(), r.count)).collect::<Vec<(Vec<String>, i64)>>(), want);
+
+        let filter = NGramFilter::new()
+            .without_sentence_tokens()
+            .with_stop_words(StopWords::english(), StopWordExclusion::Partially)
+            .with_min_word_length(5);
+        let got = bmodel.top_ngrams(3, &filter);
+        let want = vec![
+            (vec!["United".to_string(), "States".to_string()], 8),
+            (vec!["Ukrainian".to_string(), "people".to_string()], 6),
+            (vec!["American".to_string(), "Rescue".to_string()], 5)
+        ];
+        assert_eq!(got.iter().map(|r| (r.ngram.to_vec(), r.count)).collect::<Vec<(Vec<String>, i64)>>(), want);
+
+        let got = bmodel.top_ngrams(3, &NGramFilter::new().containing("jobs"));
+        let want = vec![
+            (vec!["jobs".to_string(), "</S>".to_string()], 3),
+            (vec!["American".to_string(), "jobs".to_string()], 2),
+            (vec!["jobs".to_string(), "and".to_string()], 2)
+        ];
+        assert_eq!(got.iter().map(|r| (r.ngram.to_vec(), r.count)).collect::<Vec<(Vec<String>, i64)>>(), want);
+    }
+
+    #[test]
+    fn ngram_pattern_parse() {
+        let got = NGramPattern::parse("the * (of|for)").unwrap();
+        assert_eq!(got.len(), 3);
+        assert!(got.matches(&["the".to_string(), "state".to_string(), "of".to_string()]));
+        assert!(got.matches(&["the".to_string(), "people".to_string(), "for".to_string()]));
+        assert!(!got.matches(&["a".to_string(), "state".to_string(), "of".to_string()]));
+        assert!(!got.matches(&["the".to_string(), "state".to_string()]));
+
+        assert_eq!(NGramPattern::parse("  "), Err(QueryError::EmptyPattern));
+        assert_eq!(NGramPattern::parse("(we|they"), Err(QueryError::InvalidTerm("(we|they".to_string())));
+        assert_eq!(NGramPattern::parse("we||they"), Err(QueryError::InvalidTerm("we||they".to_string())));
+    }
+
+    #[test]
+    fn query_wildcards_and_lower_orders() {
+        let mut bmodel = NGramModel::new(3);
+
+        bmodel.update_ngram_model("we will win".to_string());
+        bmodel.update_ngram_model("we will win".to_string());
+        bmodel.update_ngram_model("we will build".to_string());
+        bmodel.update_ngram_model("they will build".to_string());
+
+        let got = bmodel.query("we will *", QueryRanking::Count).unwrap();
+        assert_eq!(got.len(), 2);
+        assert_eq!(got[0].ngram, vec!["we".to_string(), "will".to_string(), "win".to_string()]);
+        assert_eq!(got[0].count, 2);
+        assert_eq!(got[0].probability, 2.0 / 3.0);
+        assert_eq!(got[1].ngram, vec!["we".to_string(), "will".to_string(), "build".to_string()]);
+
+        // Bigram pattern in a trigram model, matched against the lower order counts
+        let got = bmodel.query("(we|they) will", QueryRanking::ConditionalProbability).unwrap();
+        let want = vec![
+            (vec!["we".to_string(), "will".to_string()], 3),
+            (vec!["they".to_string(), "will".to_string()], 1)
+        ];
+        assert_eq!(got.iter().map(|m| (m.ngram.to_vec(), m.count)).collect::<Vec<(Vec<String>, i64)>>(), want);
+
+        let got = bmodel.query("* build", QueryRanking::ConditionalProbability).unwrap();
+        assert_eq!(got.len(), 1);
+        assert_eq!(got[0].probability, 2.0 / 4.0);
+
+        // Unigram probabilities are against every ngram in the model
+        let got = bmodel.query("we", QueryRanking::Count).unwrap();
+        assert_eq!(got[0].count, 3);
+        assert_eq!(got[0].probability, 3.0 / 20.0);
+
+        let got = bmodel.query("* * * *", QueryRanking::Count);
+        assert_eq!(got, Err(QueryError::PatternTooLong { pattern_length: 4, degree: 3 }));
+    }
+
+    #[test]
+    fn biden_2022_query_the_star_of() {
+        let mut bmodel = NGramModel::new(4);
+
+        // File text_sample.txt must exist in the current path
+        if let Ok(lines) = read_lines("text_samples/biden_sotu_2022.txt") {
+            // Use lines from the iterator
+            for line in lines.map_while(Result::ok) {
+                if !line.is_empty() {
+                    bmodel.update_ngram_model(line);
+                }
+            }
+        }
+
+        let got = bmodel.query("the * of", QueryRanking::Count).unwrap();
+        assert_eq!(got[0].ngram, vec!["the".to_string(), "cost".to_string(), "of".to_string()]);
+        assert_eq!(got[0].count, 4);
+        assert_eq!(got[0].probability, 0.8);
+
+        // "the cost" is sometimes followed by other words, so drops below certain continuations
+        let got = bmodel.query("the * of", QueryRanking::ConditionalProbability).unwrap();
+        assert_eq!(got[0].ngram, vec!["the".to_string(), "State".to_string(), "of".to_string()]);
+        assert_eq!(got[0].probability, 1.0);
+    }
+
+    #[test]
+    fn predict_next_padding_and_truncation() {
+        let mut bmodel = NGramModel::new(3);
+
+        bmodel.update_ngram_model("we will win".to_string());
+        bmodel.update_ngram_model("we will win".to_string());
+        bmodel.update_ngram_model("we will build".to_string());
+        bmodel.update_ngram_model("they will build".to_string());
+
+        // Empty history is padded to <S> <S>
+        let got = bmodel.predict_next("", 5);
+        let want = vec![("we".to_string(), 0.75), ("they".to_string(), 0.25)];
+        assert_eq!(got, want);
+
+        // Shorter history is padded to <S> we
+        let got = bmodel.predict_next("we", 5);
+        let want = vec![("will".to_string(), 1.0)];
+        assert_eq!(got, want);
+
+        // Longer history keeps only "we will"
+        let got = bmodel.predict_next("they said we will", 1);
+        let want = vec![("win".to_string(), 2.0 / 3.0)];
+        assert_eq!(got, want);
+
+        // Unseen context backs off to "will"
+        let got = bmodel.predict_next("you will", 5);
+        let want = vec![("build".to_string(), 0.5), ("win".to_string(), 0.5)];
+        assert_eq!(got, want);
+
+        // Nothing known, backs off to unigrams
+        let got = bmodel.predict_next("completely unseen", 2);
+        let want = vec![("will".to_string(), 4.0 / 12.0), ("we".to_string(), 3.0 / 12.0)];
+        assert_eq!(got, want);
+    }
+
+    #[test]
+    fn decoding_strategy_validate() {
+        assert!(DecodingStrategy::Greedy.validate().is_ok());
+        assert!(DecodingStrategy::Temperature(0.7).validate().is_ok());
+        assert!(DecodingStrategy::Temperature(0.0).validate().is_err());
+        assert!(DecodingStrategy::TopK(0).validate().is_err());
+        assert!(DecodingStrategy::TopP(1.0).validate().is_ok());
+        assert!(DecodingStrategy::TopP(1.5).validate().is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid decoding strategy supplied")]
+    fn generate_text_invalid_strategy() {
+        let mut bmodel = NGramModel::new(2);
+        bmodel.update_ngram_model("This year again".to_string());
+        bmodel.generate_text(DecodingStrategy::TopP(0.0), 1);
+    }
+
+    #[test]
+    fn decoding_strategy_select_word_truncation() {
+        let continuations = vec![
+            ("we".to_string(), 0.5),
+            ("they".to_string(), 0.3),
+            ("you".to_string(), 0.2)
+        ];
+        let mut rng = rand::thread_rng();
+
+        for _i in 0..100 {
+            let got = DecodingStrategy::TopK(2).select_word(&continuations, &mut rng).unwrap();
+            assert!(got == "we" || got == "they");
+
+            let got = DecodingStrategy::TopP(0.5).select_word(&continuations, &mut rng).unwrap();
+            assert_eq!(got, "we");
+
+            let got = DecodingStrategy::Temperature(2.0).select_word(&continuations, &mut rng).unwrap();
+            assert!(continuations.iter().any(|(word, _probability)| *word == got));
+        }
+
+        assert_eq!(DecodingStrategy::Probabilistic.select_word(&[], &mut rng), None);
+    }
+
+    #[test]
+    fn generate_text_trigram_biden_2024_top_k_one_is_greedy() {
+        let mut bmodel = NGramModel::new(3);
+
+        // File text_sample.txt must exist in the current path
+        if let Ok(lines) = read_lines("text_samples/biden_sotu_2024.txt") {
+            // Use lines from the iterator
+            for line in lines.map_while(Result::ok) {
+                if !line.is_empty() {
+                    bmodel.update_ngram_model(line);
+                }
+            }
+        }
+
+        let want = bmodel.generate_text(DecodingStrategy::Greedy, 1);
+        assert_eq!(bmodel.generate_text(DecodingStrategy::TopK(1), 1), want);
+        assert_eq!(bmodel.generate_text(DecodingStrategy::TopP(0.01), 1), want);
+    }
+
+    #[test]
+    fn generate_text_seeded_is_reproducible() {
+        // Two separately trained models have different HashMap orderings
+        let mut first_model = NGramModel::new(4);
+        let mut second_model = NGramModel::new(4);
+
+        // File text_sample.txt must exist in the current path
+        if let Ok(lines) = read_lines("text_samples/biden_sotu_2024.txt") {
+            // Use lines from the iterator
+            for line in lines.map_while(Result::ok) {
+                if !line.is_empty() {
+                    first_model.update_ngram_model(line.to_string());
+                    second_model.update_ngram_model(line);
+                }
+            }
+        }
+
+        let got = first_model.generate_text_seeded(DecodingStrategy::Probabilistic, 5, 42);
+        let want = second_model.generate_text_seeded(DecodingStrategy::Probabilistic, 5, 42);
+        assert_eq!(got, want);
+        // Fixed across runs, not just within one
+        assert_eq!(got[0], "Some came freely".to_string());
+
+        let got = first_model.generate_text_seeded(DecodingStrategy::TopP(0.9), 3, 7);
+        let want = second_model.generate_text_seeded(DecodingStrategy::TopP(0.9), 3, 7);
+        assert_eq!(got, want);
+
+        let mut rng = StdRng::seed_from_u64(42);
+        let got = first_model.generate_text_with_rng(DecodingStrategy::Probabilistic, 5, &mut rng);
+        let want = first_model.generate_text_seeded(DecodingStrategy::Probabilistic, 5, 42);
+        assert_eq!(got, want);
+    }
+
+    #[test]
+    fn generate_from_prompt_quadgram_biden_2022() {
+        let mut bmodel = NGramModel::new(4);
+
+        // File text_sample.txt must exist in the current path
+        if let Ok(lines) = read_lines("text_samples/biden_sotu_2022.txt") {
+            // Use lines from the iterator
+            for line in lines.map_while(Result::ok) {
+                if !line.is_empty() {
+                    bmodel.update_ngram_model(line);
+                }
+            }
+        }
+
+        // Prompt shorter than the history, padded with <S>
+        let got = bmodel.generate_from_prompt("Tonight I", DecodingStrategy::Greedy);
+        let want = "Tonight I call on Congress to fund ARPAH the Advanced Research Projects Agency for Health".to_string();
+        assert_eq!(got, want);
+
+        // Prompt longer than the history, and punctuation is cleaned like training text
+        let got = bmodel.generate_from_prompt("The state of our union is", DecodingStrategy::Greedy);
+        let want = "The state of our union is here with us tonight".to_string();
+        assert_eq!(got, want);
+
+        // Unseen context backs off
+        let got = bmodel.generate_from_prompt("Zebras are fond of the", DecodingStrategy::Greedy);
+        let want = "Zebras are fond of the 21st Century if we don’t fix that".to_string();
+        assert_eq!(got, want);
+
+        let mut rng = StdRng::seed_from_u64(1);
+        let got = bmodel.generate_from_prompt_with_rng("We will", DecodingStrategy::Probabilistic, &mut rng);
+        assert!(got.starts_with("We will "));
+    }
+
+    #[test]
+    fn beam_search_small_model() {
+        let mut bmodel = NGramModel::new(2);
+
+        bmodel.update_ngram_model("we will win".to_string());
+        bmodel.update_ngram_model("we will win".to_string());
+        bmodel.update_ngram_model("we will build".to_string());
+        bmodel.update_ngram_model("they will build".to_string());
+
+        let config = BeamSearchConfig { n_best: 4, ..BeamSearchConfig::default() };
+        let got = bmodel.beam_search(&config).unwrap();
+        let want = vec!["we will build", "we will win", "they will build", "they will win"];
+        assert_eq!(got.iter().map(|s| s.sentence.as_str()).collect::<Vec<&str>>(), want);
+
+        // P(we | <S>) P(will | we) P(build | will) P(</S> | build) = 3 / 4 * 1 * 1 / 2 * 1
+        assert_eq!(got[0].log_probability, (3.0_f64 / 8.0).log2());
+        assert_eq!(got[2].log_probability, (1.0_f64 / 8.0).log2());
+    }
+
+    #[test]
+    fn beam_search_length_normalisation_and_repetition_penalty() {
+        let mut bmodel = NGramModel::new(2);
+
+        bmodel.update_ngram_model("very very very good".to_string());
+
+        // Without normalisation the shortest sentence wins
+        let config = BeamSearchConfig { max_sentence_size: 6, ..BeamSearchConfig::default() };
+        let got = bmodel.beam_search(&config).unwrap();
+        assert_eq!(got[0].sentence, "very good".to_string());
+
+        // Strong length normalisation rewards looping on "very" as long as possible
+        let config = BeamSearchConfig { max_sentence_size: 6, length_normalisation: 2.0, ..BeamSearchConfig::default() };
+        let got = bmodel.beam_search(&config).unwrap();
+        assert_eq!(got[0].sentence, "very very very very very good".to_string());
+
+        // Unless repeated words are penalised
+        let config = BeamSearchConfig {
+            max_sentence_size: 6,
+            length_normalisation: 2.0,
+            repetition_penalty: 4.0,
+            ..BeamSearchConfig::default()
+        };
+        let got = bmodel.beam_search(&config).unwrap();
+        assert_eq!(got[0].sentence, "very good".to_string());
+        // The reported log probability is the model's, not the penalised score
+        assert_eq!(got[0].log_probability, (1.0_f64 / 3.0).log2());
+        assert_eq!(got[0].score, (1.0_f64 / 3.0).log2() / 9.0);
+    }
+
+    #[test]
+    fn beam_search_trigram_biden_2024() {
+        let mut bmodel = NGramModel::new(3);
+
+        // File text_sample.txt must exist in the current path
+        if let Ok(lines) = read_lines("text_samples/biden_sotu_2024.txt") {
+            // Use lines from the iterator
+            for line in lines.map_while(Result::ok) {
+                if !line.is_empty() {
+                    bmodel.update_ngram_model(line);
+                }
+            }
+        }
+
+        let got = bmodel.beam_search(&BeamSearchConfig::default()).unwrap();
+        assert_eq!(got.len(), 3);
+        assert_eq!(got[0].sentence, "I see a different story".to_string());
+        assert!(got.windows(2).all(|pair| pair[0].score >= pair[1].score));
+
+        let config = BeamSearchConfig { length_normalisation: 1.0, ..BeamSearchConfig::default() };
+        let got = bmodel.beam_search(&config).unwrap();
+        let want = "I see a future with affordable child care so seniors and people living with disabilities can stay in their homes and bring those rents down".to_string();
+        assert_eq!(got[0].sentence, want);
+    }
+
+    #[test]
+    fn generation_config_no_repeat_unigram_biden_2022() {
+        let mut bmodel = NGramModel::new(1);
+
+        // File text_sample.txt must exist in the current path
+        if let Ok(lines) = read_lines("text_samples/biden_sotu_2022.txt") {
+            // Use lines from the iterator
+            for line in lines.map_while(Result::ok) {
+                if !line.is_empty() {
+                    bmodel.update_ngram_model(line);
+                }
+            }
+        }
+
+        // Instead of "the the the ...", each word can only appear once
+        let config = GenerationConfig { no_repeat_ngram_size: 1, max_tokens: 5, ..GenerationConfig::default() };
+        let got = bmodel.generate_text_with_config(DecodingStrategy::Greedy, 1, &config, &mut rand::thread_rng());
+        assert_eq!(got, vec!["the to and of a".to_string()]);
+    }
+
+    #[test]
+    fn generation_config_min_tokens_and_stop_phrases() {
+        let mut bmodel = NGramModel::new(2);
+        bmodel.update_ngram_model("we will win".to_string());
+
+        let mut rng = rand::thread_rng();
+        let got = bmodel.generate_text_with_config(DecodingStrategy::Greedy, 1, &GenerationConfig::default(), &mut rng);
+        assert_eq!(got, vec!["we will win".to_string()]);
+
+        // </S> is forbidden after "win", so generation backs off to unigrams
+        let config = GenerationConfig { min_tokens: 5, ..GenerationConfig::default() };
+        let got = bmodel.generate_text_with_config(DecodingStrategy::Greedy, 1, &config, &mut rng);
+        assert_eq!(got, vec!["we will win we will win".to_string()]);
+
+        let config = GenerationConfig { max_tokens: 2, ..GenerationConfig::default() };
+        let got = bmodel.generate_text_with_config(DecodingStrategy::Greedy, 1, &config, &mut rng);
+        assert_eq!(got, vec!["we will".to_string()]);
+
+        let config = GenerationConfig { stop_phrases: vec!["will!".to_string()], ..GenerationConfig::default() };
+        let got = bmodel.generate_from_prompt_with_config("So we", DecodingStrategy::Greedy, &config, &mut rng);
+        assert_eq!(got, "So we will".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid generation config supplied")]
+    fn generation_config_invalid() {
+        let mut bmodel = NGramModel::new(2);
+        bmodel.update_ngram_model("we will win".to_string());
+
+        let config = GenerationConfig { min_tokens: 10, max_tokens: 5, ..GenerationConfig::default() };
+        bmodel.generate_text_with_config(DecodingStrategy::Greedy, 1, &config, &mut rand::thread_rng());
+    }
+
+    #[test]
+    fn generate_constrained_trigram_biden_2024() {
+        let mut bmodel = NGramModel::new(3);
+
+        // File text_sample.txt must exist in the current path
+        if let Ok(lines) = read_lines("text_samples/biden_sotu_2024.txt") {
+            // Use lines from the iterator
+            for line in lines.map_while(Result::ok) {
+                if !line.is_empty() {
+                    bmodel.update_ngram_model(line);
+                }
+            }
+        }
+
+        let constraints = LexicalConstraints {
+            required_words: vec!["economy".to_string(), "freedom".to_string()],
+            banned_words: vec!["I".to_string()]
+        };
+        let config = BeamSearchConfig { beam_width: 10, ..BeamSearchConfig::default() };
+        let got = bmodel.generate_constrained(&constraints, &config).unwrap();
+        let words: Vec<&str> = got.sentence.split(' ').collect();
+        assert!(words.contains(&"economy"));
+        assert!(words.contains(&"freedom"));
+        assert!(!words.contains(&"I"));
+        assert!(words.len() <= config.max_sentence_size);
+
+        let constraints = LexicalConstraints { required_words: vec!["democracy".to_string()], banned_words: vec![] };
+        let config = BeamSearchConfig { length_normalisation: 1.0, ..BeamSearchConfig::default() };
+        let got = bmodel.generate_constrained(&constraints, &config).unwrap();
+        let want = "I see a future with affordable child care so seniors and people living with disabilities can stay in their homes and democracy must be defended".to_string();
+        assert_eq!(got.sentence, want);
+    }
+
+    #[test]
+    fn generate_constrained_errors() {
+        let mut bmodel = NGramModel::new(2);
+        bmodel.update_ngram_model("we will win".to_string());
+        bmodel.update_ngram_model("they will build".to_string());
+        let config = BeamSearchConfig::default();
+
+        let constraints = LexicalConstraints { required_words: vec!["zebra".to_string()], banned_words: vec![] };
+        assert_eq!(bmodel.generate_constrained(&constraints, &config), Err(ConstraintError::UnknownWord("zebra".to_string())));
+
+        let constraints = LexicalConstraints { required_words: vec!["win".to_string()], banned_words: vec!["win".to_string()] };
+        assert_eq!(bmodel.generate_constrained(&constraints, &config), Err(ConstraintError::ConflictingConstraint("win".to_string())));
+
+        let constraints = LexicalConstraints { required_words: vec!["we will".to_string()], banned_words: vec![] };
+        assert_eq!(bmodel.generate_constrained(&constraints, &config), Err(ConstraintError::InvalidConstraint("we will".to_string())));
+
+        // Both words can't fit in a one word sentence
+        let constraints = LexicalConstraints { required_words: vec!["win".to_string(), "build".to_string()], banned_words: vec![] };
+        let config = BeamSearchConfig { max_sentence_size: 1, ..BeamSearchConfig::default() };
+        assert_eq!(bmodel.generate_constrained(&constraints, &config), Err(ConstraintError::NoValidSentence { max_sentence_size: 1 }));
+
+        // Every sentence needs "will", so banning it leaves only forced, backed off words
+        let constraints = LexicalConstraints { required_words: vec!["build".to_string()], banned_words: vec!["will".to_string()] };
+        let got = bmodel.generate_constrained(&constraints, &BeamSearchConfig::default()).unwrap();
+        assert_eq!(got.sentence, "build".to_string());
+
+        let config = BeamSearchConfig { beam_width: 0, ..BeamSearchConfig::default() };
+        let got = bmodel.generate_constrained(&constraints, &config);
+        assert_eq!(got, Err(ConstraintError::InvalidConfig("beam_width must be at least 1".to_string())));
+    }
+
+    #[test]
+    fn beam_search_rejects_invalid_config() {
+        let mut bmodel = NGramModel::new(2);
+        bmodel.update_ngram_model("we will win".to_string());
+
+        let config = BeamSearchConfig { beam_width: 0, ..BeamSearchConfig::default() };
+        assert_eq!(bmodel.beam_search(&config), Err("beam_width must be at least 1".to_string()));
+
+        for repetition_penalty in [0.5, 0.0, -1.0, f64::NAN, f64::INFINITY] {
+            let config = BeamSearchConfig { repetition_penalty, ..BeamSearchConfig::default() };
+            assert!(bmodel.beam_search(&config).is_err());
+            let constraints = LexicalConstraints { required_words: vec!["win".to_string()], banned_words: vec![] };
+            assert!(matches!(bmodel.generate_constrained(&constraints, &config), Err(ConstraintError::InvalidConfig(_))));
+        }
+
+        let config = BeamSearchConfig { beam_width: 1, repetition_penalty: 1.0, ..BeamSearchConfig::default() };
+        assert_eq!(bmodel.beam_search(&config).unwrap()[0].sentence, "we will win".to_string());
+    }
+
+    #[test]
+    fn update_ngram_model_document_keeps_cross_sentence_context() {
+        let mut bmodel = NGramModel::new(3);
+
+        let paragraphs = vec![
+            "Last year we were apart. This year we are together!".to_string(),
+            "".to_string(),
+            "Tonight we meet.".to_string()
+        ];
+        bmodel.update_ngram_model_document(&paragraphs);
+
+        // Sentences and paragraphs follow on from each other, with no start tokens in between
+        let got = &bmodel.ngram_counts;
+        assert_eq!(got.len(), 18);
+        assert!(got.contains_key(&vec!["apart".to_string(), "</S>".to_string(), "This".to_string()]));
+        assert!(got.contains_key(&vec!["together".to_string(), "</S>".to_string(), "<P>".to_string()]));
+        assert!(got.contains_key(&vec!["</S>".to_string(), "<P>".to_string(), "Tonight".to_string()]));
+        assert!(got.contains_key(&vec!["meet".to_string(), "</S>".to_string(), "</S>".to_string()]));
+        assert!(!got.contains_key(&vec!["<S>".to_string(), "<S>".to_string(), "This".to_string()]));
+
+        // Paragraph breaks count as sentence tokens
+        let got = bmodel.most_common_ngram_without_sentence_tokens().unwrap();
+        assert_eq!(*got.0, vec!["Last".to_string(), "year".to_string(), "we".to_string()]);
+    }
+
+    #[test]
+    fn update_ngram_model_document_ends_bigram_documents() {
+        let mut bmodel = NGramModel::new(2);
+        let paragraphs = vec!["We meet here today. We part.".to_string()];
+        bmodel.update_ngram_model_document(&paragraphs);
+
+        let got = &bmodel.ngram_counts;
+        assert!(got.contains_key(&vec!["part".to_string(), "</S>".to_string()]));
+        assert!(got.contains_key(&vec!["</S>".to_string(), "</S>".to_string()]));
+
+        // The end of the document starts generation again with a new paragraph, and
+        // sentences are cut off at the config's max_tokens
+        let config = GenerationConfig { max_tokens: 2, ..GenerationConfig::default() };
+        let mut rng = StdRng::seed_from_u64(1);
+        let got = bmodel.generate_paragraphs_with_config(DecodingStrategy::Probabilistic, 2, &config, &mut rng);
+        assert_eq!(got.len(), 2);
+        for paragraph in got {
+            assert!(paragraph.split('.').all(|sentence| sentence.split_whitespace().count() <= 2));
+        }
+    }
+
+    #[test]
+    fn generate_paragraphs_ends_cycling_paragraphs() {
+        let mut bmodel = NGramModel::new(2);
+        bmodel.update_ngram_model_document(&["the cat sat. the cat sat. the cat ran.".to_string()]);
+
+        // Greedy decoding goes round "the cat sat." forever, never reaching <P> or the end of the document
+        let mut rng = StdRng::seed_from_u64(1);
+        let got = bmodel.generate_paragraphs_with_rng(DecodingStrategy::Greedy, 2, &mut rng);
+        let expected = vec![["the cat sat."; 10].join(" "); 2];
+        assert_eq!(got, expected);
+    }
+
+    #[test]
+    fn generate_speech_quadgram_biden_2022() {
+        let mut bmodel = NGramModel::new(4);
+
+        // File text_sample.txt must exist in the current path
+        if let Ok(lines) = read_lines("text_samples/biden_sotu_2022.txt") {
+            let paragraphs: Vec<String> = lines.map_while(Result::ok).collect();
+            bmodel.update_ngram_model_document(&paragraphs);
+        }
+
+        let mut rng = StdRng::seed_from_u64(3);
+        let got = bmodel.generate_paragraphs_with_rng(DecodingStrategy::Probabilistic, 3, &mut rng);
+        assert_eq!(got.len(), 3);
+        assert!(got.iter().all(|paragraph| paragraph.ends_with('.')));
+
+        let config = SpeechConfig { target_word_count: 200, ..SpeechConfig::default() };
+        let got = bmodel.generate_speech_with_rng(DecodingStrategy::Probabilistic, &config, &mut rng);
+        let paragraphs: Vec<&str> = got.split("\n\n").collect();
+        assert!(paragraphs.len() > 3);
+        assert_eq!(*paragraphs.first().unwrap(), config.salutation);
+        assert_eq!(*paragraphs.last().unwrap(), config.closing);
+
+        let body_word_count: usize = paragraphs[1..paragraphs.len() - 1]
+            .iter()
+            .map(|paragraph| paragraph.split_whitespace().count())
+            .sum();
+        assert!(body_word_count >= 200);
+        // Stops at the end of the sentence that reaches the target
+        assert!(body_word_count < 200 + config.max_sentence_size);
+    }
+
+    #[test]
+    fn update_ngram_model_reversed() {
+        let mut bmodel = NGramModel::new_reversed(2);
+        assert!(bmodel.is_reversed());
+
+        bmodel.update_ngram_model("We will win.".to_string());
+
+        // The sentence is read right to left, so the start padding sits at its end
+        let got = &bmodel.ngram_counts;
+        assert_eq!(got.len(), 4);
+        assert!(got.contains_key(&vec!["<S>".to_string(), "win".to_string()]));
+        assert!(got.contains_key(&vec!["win".to_string(), "will".to_string()]));
+        assert!(got.contains_key(&vec!["will".to_string(), "We".to_string()]));
+        assert!(got.contains_key(&vec!["We".to_string(), "</S>".to_string()]));
+
+        // Predicts the word before the history
+        let got = bmodel.predict_next("will win", 1);
+        assert_eq!(got, vec![("We".to_string(), 1.0)]);
+    }
+
+    #[test]
+    fn bidirectional_generate_ending_with() {
+        let mut bmodel = BidirectionalModel::new(3);
+
+        bmodel.update_ngram_model("May God bless America.".to_string());
+        bmodel.update_ngram_model("We will win.".to_string());
+
+        let mut rng = StdRng::seed_from_u64(1);
+        let got = bmodel.generate_ending_with_rng("bless America", DecodingStrategy::Greedy, &mut rng);
+        assert_eq!(got, "May God bless America".to_string());
+    }
+
+    #[test]
+    fn bidirectional_generate_around() {
+        let mut bmodel = BidirectionalModel::new(3);
+
+        bmodel.update_ngram_model("We will win.".to_string());
+        bmodel.update_ngram_model("Together we will build.".to_string());
+        bmodel.update_ngram_model("They will build.".to_string());
+
+        let mut rng = StdRng::seed_from_u64(1);
+        let got = bmodel.generate_around_with_rng("will", DecodingStrategy::Greedy, &mut rng);
+        assert_eq!(got, "They will build".to_string());
+
+        // Without a seed the sentence is generated forwards from its start
+        let got = bmodel.generate_around_with_rng("", DecodingStrategy::Greedy, &mut rng);
+        let expected = bmodel.forward.generate_text_with_rng(DecodingStrategy::Greedy, 1, &mut rng);
+        assert_eq!(got, expected[0]);
+        assert_eq!(got, "They will build".to_string());
+    }
+
+    #[test]
+    fn bidirectional_generate_around_quadgram_biden_2022() {
+        let mut bmodel = BidirectionalModel::new(4);
+
+        // File text_sample.txt must exist in the current path
+        if let Ok(lines) = read_lines("text_samples/biden_sotu_2022.txt") {
+            for line in lines.map_while(Result::ok) {
+                if !line.is_empty() {
+                    bmodel.update_ngram_model(line);
+                }
+            }
+        }
+
+        let mut rng = StdRng::seed_from_u64(5);
+        for _i in 0..5 {
+            let got = bmodel.generate_around_with_rng("democracy", DecodingStrategy::Probabilistic, &mut rng);
+            assert!(got.split_whitespace().any(|word| word == "democracy"));
+        }
+
+        let got = bmodel.generate_ending_with_rng("God bless you all", DecodingStrategy::Probabilistic, &mut rng);
+        assert!(got.ends_with("God bless you all"));
+    }
+
+    #[test]
+    fn stream_tokens_bigram() {
+        let mut bmodel = NGramModel::new(2);
+
+        bmodel.update_ngram_model("We will win.".to_string());
+        bmodel.update_ngram_model("We will build.".to_string());
+        bmodel.update_ngram_model("We will build.".to_string());
+
+        let rng = StdRng::seed_from_u64(1);
+        let got: Vec<GeneratedToken> = bmodel
+            .stream_tokens_with_rng(DecodingStrategy::Greedy, GenerationConfig::default(), rng)
+            .collect();
+        let expected = vec![
+            GeneratedToken { token: "We".to_string(), probability: 1.0, history: vec!["<S>".to_string()] },
+            GeneratedToken { token: "will".to_string(), probability: 1.0, history: vec!["We".to_string()] },
+            GeneratedToken { token: "build".to_string(), probability: 2.0 / 3.0, history: vec!["will".to_string()] }
+        ];
+        assert_eq!(got, expected);
+
+        // The caller can stop early
+        let rng = StdRng::seed_from_u64(1);
+        let mut stream = bmodel.stream_from_prompt_with_rng("We", DecodingStrategy::Greedy, GenerationConfig::default(), rng);
+        let got = stream.next().unwrap();
+        assert_eq!(got.token, "will".to_string());
+        assert_eq!(stream.generated(), &["will".to_string()]);
+    }
+
+    #[test]
+    fn stream_tokens_matches_generate_text_trigram_biden_2024() {
+        let mut bmodel = NGramModel::new(3);
+
+        // File text_sample.txt must exist in the current path
+        if let Ok(lines) = read_lines("text_samples/biden_sotu_2024.txt") {
+            for line in lines.map_while(Result::ok) {
+                if !line.is_empty() {
+                    bmodel.update_ngram_model(line);
+                }
+            }
+        }
+
+        let config = GenerationConfig { no_repeat_ngram_size: 2, ..GenerationConfig::default() };
+        let mut rng = StdRng::seed_from_u64(7);
+        let expected = bmodel.generate_text_with_config(DecodingStrategy::TopK(5), 1, &config, &mut rng);
+
+        let mut rng = StdRng::seed_from_u64(7);
+        let got: Vec<String> = bmodel
+            .stream_tokens_with_rng(DecodingStrategy::TopK(5), config, &mut rng)
+            .map(|generated| generated.token)
+            .collect();
+        assert_eq!(got.join(" "), expected[0]);
+    }
+
+    #[test]
+    fn novelty_index_finds_longest_copy() {
+        let mut bmodel = NGramModel::new(2);
+        assert!(bmodel.novelty_index().is_none());
+
+        bmodel.record_source_sentences();
+        bmodel.update_ngram_model("We will win.".to_string());
+        bmodel.update_ngram_model("Together we will build a future.".to_string());
+        assert_eq!(bmodel.source_sentences().unwrap().len(), 2);
+
+        let index = bmodel.novelty_index().unwrap();
+        let got = index.check("They say we will build a bridge");
+        let expected = CopiedSpan {
+            words: vec!["we".to_string(), "will".to_string(), "build".to_string(), "a".to_string()],
+            position: 2,
+            source_sentence: 1,
+            source_position: 1
+        };
+        assert_eq!(got.longest_copy, Some(expected));
+        assert_eq!(got.copy_ratio, 4.0 / 7.0);
+        assert!(!got.is_verbatim());
+
+        // Matches don't run across sentence boundaries
+        let got = index.check("We will win Together");
+        assert_eq!(got.longest_copy.unwrap().words.len(), 3);
+        assert!(index.check("We will win").is_verbatim());
+
+        let got = index.check("Nothing here");
+        assert_eq!(got.longest_copy, None);
+        assert_eq!(got.copy_ratio, 0 as f64);
+    }
+
+    #[test]
+    fn generate_novel_text_quadgram_biden_2022() {
+        let mut bmodel = NGramModel::new(4);
+        bmodel.record_source_sentences();
+
+        // File text_sample.txt must exist in the current path
+        if let Ok(lines) = read_lines("text_samples/biden_sotu_2022.txt") {
+            for line in lines.map_while(Result::ok) {
+                if !line.is_empty() {
+                    bmodel.update_ngram_model(line);
+                }
+            }
+        }
+
+        let index = bmodel.novelty_index().unwrap();
+        let config = NoveltyConfig { max_copy_ratio: 0.9, max_attempts: 50 };
+        let mut rng = StdRng::seed_from_u64(11);
+        let got = bmodel.generate_novel_text_with_rng(DecodingStrategy::Probabilistic, 5, &index, &config, &mut rng);
+        assert!(!got.is_empty());
+        for sentence in got {
+            let report = index.check(&sentence);
+            assert!(report.copy_ratio <= 0.9);
+            assert!(!report.is_verbatim());
+        }
+    }
+
+    // Serves the responses in order, one per connection, and returns the requests received
+    fn serve_responses(
+        responses: Vec<String>
+    ) -> (String, thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let handle = thread::spawn(move || {
+            let mut requests: Vec<String> = vec![];
+            for response in responses {
+                let (mut stream, _address) = listener.accept().unwrap();
+                let mut request: Vec<u8> = vec![];
+                let mut buffer = [0; 1024];
+                while !request.ends_with(b"\r\n\r\n") {
+                    let read = stream.read(&mut buffer).unwrap();
+                    if read == 0 {
+                        break;
+                    }
+                    request.extend_from_slice(&buffer[..read]);
+                }
+                requests.push(String::from_utf8_lossy(&request).to_string());
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+            requests
+        });
+        (base_url, handle)
+    }
+
+    fn temp_cache_directory(
+        name: &str
+    ) -> std::path::PathBuf {
+        let directory = std::env::temp_dir().join(name);
+        let _ = std::fs::remove_dir_all(&directory);
+        directory
+    }
+
+    #[test]
+    fn http_cache_revalidates_with_etag() {
+        let directory = temp_cache_directory("grams_http_cache_revalidates_with_etag");
+        let (base_url, server) = serve_responses(vec![
+            "HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nLast-Modified: Tue, 01 Mar 2022 21:00:00 GMT\r\nContent-Length: 12\r\nConnection: close\r\n\r\nWe will win.".to_string(),
+            "HTTP/1.1 304 Not Modified\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string()
+        ]);
+        let url = format!("{}/address", base_url);
+
+        let mut cache = HttpCache::new(&directory);
+        assert_eq!(cache.get(&url).unwrap(), "We will win.".to_string());
+        assert!(cache.contains(&url));
+
+        // Fresh pages don't touch the server
+        assert_eq!(cache.get(&url).unwrap(), "We will win.".to_string());
+
+        // Stale pages are revalidated, and kept when unchanged
+        cache.max_age = std::time::Duration::ZERO;
+        assert_eq!(cache.get(&url).unwrap(), "We will win.".to_string());
+
+        let requests = server.join().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert!(!requests[0].to_lowercase().contains("if-none-match"));
+        assert!(requests[1].to_lowercase().contains("if-none-match: \"v1\""));
+        assert!(requests[1].to_lowercase().contains("if-modified-since: tue, 01 mar 2022 21:00:00 gmt"));
+    }
+
+    #[test]
+    fn gather_text_offline_from_cache() {
+        let directory = temp_cache_directory("grams_gather_text_offline_from_cache");
+        let cache = HttpCache::offline(&directory);
+
+        // Nothing is downloaded in offline mode
+        let got = cache.get("https://www.presidency.ucsb.edu/documents/missing");
+        let expected = CacheError::MissingPage("https://www.presidency.ucsb.edu/documents/missing".to_string());
+        assert_eq!(got, Err(ScrapeError::Cache(expected)));
+
+        cache.insert(
+            "https://www.presidency.ucsb.edu/documents/presidential-documents-archive-guidebook/annual-messages-congress-the-state-the-union",
+            r#"<table><tr><td><a href="https://www.presidency.ucsb.edu/documents/address-1">2022</a></td></tr></table>"#
+        ).unwrap();
+        cache.insert(
+            "https://www.presidency.ucsb.edu/documents/address-1",
+            r#"<div class="field-docs-content"><p>We will win. Together we build.</p></div>"#
+        ).unwrap();
+
+        let mut sotu_scraper = SOTUScraper::gather_text_cached(&cache).unwrap();
+        let got: Vec<&String> = sotu_scraper.get_line_iterator().collect();
+        assert_eq!(got, vec!["We will win", "Together we build"]);
+    }
+
+    #[test]
+    fn http_cache_checks_the_url_of_an_entry() {
+        let directory = temp_cache_directory("grams_http_cache_checks_the_url_of_an_entry");
+        let url = "https://www.presidency.ucsb.edu/documents/address-1";
+        let cache = HttpCache::offline(&directory);
+        cache.insert(url, "We will win.").unwrap();
+        assert_eq!(cache.get(url).unwrap(), "We will win.".to_string());
+
+        // Stand in for a hash collision by pointing the entry at another URL
+        let entry_path = std::fs::read_dir(&directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .find(|path| path.extension().is_some_and(|extension| extension == "meta"))
+            .unwrap();
+        let entry = std::fs::read_to_string(&entry_path).unwrap();
+        std::fs::write(&entry_path, entry.replace(url, "https://www.presidency.ucsb.edu/documents/address-2")).unwrap();
+
+        assert!(!cache.contains(url));
+        assert_eq!(cache.get(url), Err(ScrapeError::Cache(CacheError::MissingPage(url.to_string()))));
+
+        // Inserting the page again takes the files back
+        cache.insert(url, "Together we build.").unwrap();
+        assert_eq!(cache.get(url).unwrap(), "Together we build.".to_string());
+    }
+
+    #[test]
+    fn http_cache_skips_error_pages() {
+        let directory = temp_cache_directory("grams_http_cache_skips_error_pages");
+        let (base_url, server) = serve_responses(vec![
+            "HTTP/1.1 404 Not Found\r\nContent-Length: 9\r\nConnection: close\r\n\r\nNot found".to_string(),
+            "HTTP/1.1 200 OK\r\nContent-Length: 12\r\nConnection: close\r\n\r\nWe will win.".to_string()
+        ]);
+        let url = format!("{}/address", base_url);
+
+        let cache = HttpCache::new(&directory);
+        assert!(cache.get(&url).is_err());
+        assert!(!cache.contains(&url));
+
+        // So the next request asks the server again
+        assert_eq!(cache.get(&url).unwrap(), "We will win.".to_string());
+        assert_eq!(server.join().unwrap().len(), 2);
+    }
+
+    // A stand-in for the UCSB site serving the fixture pages. Runs until the tests finish,
+    // and records the path of every request
+    fn serve_fixture_site() -> (String, Arc<Mutex<Vec<String>>>) {
+        let fixture = |name: &str| std::fs::read_to_string(format!("tests/fixtures/ucsb/{}", name)).unwrap();
+        let pages: Vec<(String, String)> = vec![
+            (Collection::state_of_the_union().directory_path, fixture("directory.html")),
+            ("/documents/address-before-joint-session-the-congress-the-state-the-union-27".to_string(), fixture("address-2020.html")),
+            ("/documents/address-before-joint-session-the-congress-the-state-the-union-28".to_string(), fixture("address-2022.html")),
+            ("/documents/address-before-joint-session-the-congress-the-state-the-union-29".to_string(), fixture("address-2023.html")),
+            ("/documents/broken-directory".to_string(), fixture("directory-with-broken-links.html")),
+            (Collection::inaugural_addresses().directory_path, fixture("inaugural-directory.html")),
+            ("/documents/inaugural-address-53".to_string(), fixture("inaugural-2021.html")),
+            ("/documents/page-without-content".to_string(), "<html><body><p>Page not found</p></body></html>".to_string())
+        ];
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let requested_paths = Arc::new(Mutex::new(vec![]));
+        let server_requested_paths = Arc::clone(&requested_paths);
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut request: Vec<u8> = vec![];
+                let mut buffer = [0; 1024];
+                while !request.ends_with(b"\r\n\r\n") {
+                    let read = stream.read(&mut buffer).unwrap();
+                    if read == 0 {
+                        break;
+                    }
+                    request.extend_from_slice(&buffer[..read]);
+                }
+                let request = String::from_utf8_lossy(&request).to_string();
+                let path = request.split_whitespace().nth(1).unwrap_or("").to_string();
+
+                let response = match pages.iter().find(|(page_path, _body)| *page_path == path) {
+                    Some((_path, body)) => format!("HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", body.len(), body),
+                    None => "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string()
+                };
+                server_requested_paths.lock().unwrap().push(path);
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+        (base_url, requested_paths)
+    }
+
+    #[test]
+    fn gather_text_from_local_mirror() {
+        let (base_url, requested_paths) = serve_fixture_site();
+        let config = ScraperConfig { base_url, ..ScraperConfig::default() };
+
+        let mut sotu_scraper = SOTUScraper::gather_text_with_config(&config).unwrap();
+        let got: Vec<&String> = sotu_scraper.get_line_iterator().collect();
+        let expected = vec![
+            "Three years ago, we launched the great American comeback",
+            "Madam Speaker, Madam Vice President, our First Lady and Second Gentleman",
+            "Members of Congress and the Cabinet",
+            "Last year COVID-19 kept us apart",
+            "This year we are finally together again",
+            "The story of America is a story of progress and resilience"
+        ];
+        assert_eq!(got, expected);
+
+        // Only the directory and the addresses linked from its tables are downloaded
+        assert_eq!(requested_paths.lock().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn gather_text_from_local_mirror_cached_then_offline() {
+        let (base_url, requested_paths) = serve_fixture_site();
+        let directory = temp_cache_directory("grams_gather_text_from_local_mirror_cached_then_offline");
+
+        let config = ScraperConfig {
+            base_url: base_url.to_string(),
+            cache: Some(HttpCache::new(&directory)),
+            ..ScraperConfig::default()
+        };
+        let mut online = SOTUScraper::gather_text_with_config(&config).unwrap();
+        assert_eq!(requested_paths.lock().unwrap().len(), 4);
+
+        let config = ScraperConfig {
+            base_url,
+            cache: Some(HttpCache::offline(&directory)),
+            ..ScraperConfig::default()
+        };
+        let mut offline = SOTUScraper::gather_text_with_config(&config).unwrap();
+        assert_eq!(requested_paths.lock().unwrap().len(), 4);
+        assert!(online.get_line_iterator().eq(offline.get_line_iterator()));
+    }
+
+    #[test]
+    fn sotu_document_from_html() {
+        let html = std::fs::read_to_string("tests/fixtures/ucsb/address-2022.html").unwrap();
+        let got = SotuDocument::from_html("https://www.presidency.ucsb.edu/documents/2022", &html, &ScraperConfig::default()).unwrap();
+        let expected = SotuDocument {
+            president: "Joseph R. Biden, Jr.".to_string(),
+            date: "March 01, 2022".to_string(),
+            year: Some(2022),
+            title: "Address Before a Joint Session of the Congress on the State of the Union".to_string(),
+            url: "https://www.presidency.ucsb.edu/documents/2022".to_string(),
+            collection: String::new(),
+            categories: vec![],
+            paragraphs: vec![
+                "Madam Speaker, Madam Vice President, our First Lady and Second Gentleman. Members of Congress and the Cabinet.".to_string(),
+                "Last year COVID-19 kept us apart. This year we are finally together again.".to_string()
+            ]
+        };
+        assert_eq!(got, expected);
+        assert_eq!(got.lines()[1], "Members of Congress and the Cabinet".to_string());
+
+        // Metadata is optional, the content isn't
+        let got = SotuDocument::from_html("url", r#"<div class="field-docs-content"><p>We will win.</p></div>"#, &ScraperConfig::default()).unwrap();
+        assert_eq!(got.president, "".to_string());
+        assert_eq!(got.year, None);
+        let got = SotuDocument::from_html("url", "<p>We will win.</p>", &ScraperConfig::default());
+        assert_eq!(got, Err(ScrapeError::MissingContent { url: "url".to_string() }));
+    }
+
+    #[test]
+    fn gather_documents_from_local_mirror() {
+        let (base_url, _requested_paths) = serve_fixture_site();
+        let config = ScraperConfig { base_url: base_url.to_string(), ..ScraperConfig::default() };
+
+        let sotu_scraper = SOTUScraper::gather_text_with_config(&config).unwrap();
+        let got: Vec<(&str, Option<i32>)> = sotu_scraper
+            .documents()
+            .iter()
+            .map(|document| (document.president.as_str(), document.year))
+            .collect();
+        let expected = vec![
+            ("Donald J. Trump", Some(2020)),
+            ("Joseph R. Biden, Jr.", Some(2022)),
+            ("Joseph R. Biden, Jr.", Some(2023))
+        ];
+        assert_eq!(got, expected);
+        assert_eq!(sotu_scraper.documents()[0].url, format!("{}/documents/address-before-joint-session-the-congress-the-state-the-union-27", base_url));
+    }
+
+    fn sotu_document(
+        president: &str,
+        year: i32,
+        title: &str,
+        paragraphs: &[&str]
+    ) -> SotuDocument {
+        SotuDocument {
+            president: president.to_string(),
+            date: year.to_string(),
+            year: Some(year),
+            title: title.to_string(),
+            url: format!("https://www.presidency.ucsb.edu/documents/{}", year),
+            collection: "state-of-the-union".to_string(),
+            categories: vec![],
+            paragraphs: paragraphs.iter().map(|paragraph| paragraph.to_string()).collect()
+        }
+    }
+
+    #[test]
+    fn corpus_filter() {
+        let corpus = Corpus::new(vec![
+            sotu_document("Abraham Lincoln", 1862, "Annual Message to Congress", &["We cannot escape history."]),
+            sotu_document("Franklin D. Roosevelt", 1941, "Annual Message to Congress on the State of the Union", &["Freedom of speech."]),
+            sotu_document("Lyndon B. Johnson", 1964, "Annual Message to the Congress on the State of the Union", &["Unconditional war on poverty."]),
+            sotu_document("Richard Nixon", 1973, "State of the Union Message to the Congress", &["Written this year."]),
+            sotu_document("Joseph R. Biden, Jr.", 2022, "Address Before a Joint Session of the Congress on the State of the Union", &["We will win."])
+        ]);
+        let years = |corpus: &Corpus| corpus.documents().iter().map(|document| document.year.unwrap()).collect::<Vec<i32>>();
+
+        assert_eq!(years(&corpus.filter(&CorpusFilter::new())), vec![1862, 1941, 1964, 1973, 2022]);
+        assert_eq!(years(&corpus.filter(&CorpusFilter::new().with_president("lincoln"))), vec![1862]);
+        // Whole words only, so "John" doesn't match "Johnson"
+        assert!(corpus.filter(&CorpusFilter::new().with_president("John")).is_empty());
+        assert_eq!(years(&corpus.filter(&CorpusFilter::new().with_party(Party::Democratic))), vec![1941, 1964, 2022]);
+        assert_eq!(years(&corpus.filter(&CorpusFilter::new().with_years(1945, 1990))), vec![1964, 1973]);
+        assert_eq!(years(&corpus.filter(&CorpusFilter::new().with_delivery_type(DeliveryType::Spoken))), vec![1941, 1964, 2022]);
+
+        let filter = CorpusFilter::new()
+            .with_party(Party::Republican)
+            .with_years(1945, 1990)
+            .with_delivery_type(DeliveryType::Written);
+        assert_eq!(years(&corpus.filter(&filter)), vec![1973]);
+
+        assert_eq!(corpus.documents()[4].party(), Some(Party::Democratic));
+        assert_eq!(corpus.documents()[3].party(), Some(Party::Republican));
+    }
+
+    #[test]
+    fn delivery_type_by_year() {
+        let spoken = vec![
+            sotu_document("George Washington", 1790, "First Annual Address to Congress", &[]),
+            sotu_document("Harry S. Truman", 1947, "Annual Message to the Congress on the State of the Union", &[]),
+            sotu_document("Harry S. Truman", 1948, "Annual Message to the Congress on the State of the Union", &[]),
+            sotu_document("Dwight D. Eisenhower", 1953, "Annual Message to the Congress on the State of the Union", &[]),
+            sotu_document("Dwight D. Eisenhower", 1954, "Annual Message to the Congress on the State of the Union", &[]),
+            sotu_document("John F. Kennedy", 1961, "Annual Message to the Congress on the State of the Union", &[]),
+            sotu_document("John F. Kennedy", 1963, "Annual Message to the Congress on the State of the Union", &[]),
+            sotu_document("Lyndon B. Johnson", 1964, "Annual Message to the Congress on the State of the Union", &[])
+        ];
+        for document in &spoken {
+            assert_eq!(document.delivery_type(), DeliveryType::Spoken, "{} {:?}", document.president, document.year);
+        }
+
+        let written = vec![
+            sotu_document("Abraham Lincoln", 1862, "Annual Message to Congress", &[]),
+            sotu_document("Dwight D. Eisenhower", 1956, "Annual Message to the Congress on the State of the Union", &[]),
+            sotu_document("Richard Nixon", 1973, "State of the Union Message to the Congress", &[]),
+            sotu_document("Jimmy Carter", 1981, "The State of the Union Annual Message to the Congress", &[])
+        ];
+        for document in &written {
+            assert_eq!(document.delivery_type(), DeliveryType::Written, "{} {:?}", document.president, document.year);
+        }
+
+        // Other collections go by their titles
+        let mut inaugural = sotu_document("Abraham Lincoln", 1861, "Inaugural Address", &[]);
+        inaugural.collection = "inaugural".to_string();
+        assert_eq!(inaugural.delivery_type(), DeliveryType::Spoken);
+    }
+
+    #[test]
+    fn delivery_type_by_category() {
+        let page = |title: &str, categories: &[&str]| {
+            let links: String = categories
+                .iter()
+                .map(|category| format!(r#"<a href="/documents/app-categories/{}">{}</a>"#, category.to_lowercase().replace(' ', "-"), category))
+                .collect();
+            format!(
+                r#"<div class="field-ds-doc-title"><h1>{}</h1></div>
+<div class="field-docs-start-date-time"><span class="date-display-single" content="1978-01-19T12:00:00+00:00">January 19, 1978</span></div>
+<div class="field-docs-content"><p>The state of our Union is sound.</p></div>
+<div class="group-meta">{}</div>"#,
+                title, links
+            )
+        };
+
+        // Two State of the Union documents from the same year, a speech and a written message
+        let html = page("The State of the Union Address Delivered Before a Joint Session of the Congress", &["Spoken Addresses and Remarks", "State of the Union Addresses"]);
+        let spoken = SotuDocument::from_html("https://www.presidency.ucsb.edu/documents/1978-address", &html, &ScraperConfig::default()).unwrap();
+        let html = page("The State of the Union Annual Message to the Congress", &["Written Messages", "State of the Union Written Messages"]);
+        let written = SotuDocument::from_html("https://www.presidency.ucsb.edu/documents/1978-message", &html, &ScraperConfig::default()).unwrap();
+        assert_eq!(spoken.year, Some(1978));
+        assert_eq!(written.year, Some(1978));
+        assert_eq!(written.categories, vec!["Written Messages".to_string(), "State of the Union Written Messages".to_string()]);
+        assert_eq!(spoken.delivery_type(), DeliveryType::Spoken);
+        assert_eq!(written.delivery_type(), DeliveryType::Written);
+
+        // Categories win over the years speeches were given, for the outgoing presidents' final messages too
+        let mut truman = sotu_document("Harry S. Truman", 1953, "Annual Message to the Congress on the State of the Union", &[]);
+        assert_eq!(truman.delivery_type(), DeliveryType::Spoken);
+        truman.categories = vec!["Written Messages".to_string()];
+        assert_eq!(truman.delivery_type(), DeliveryType::Written);
+    }
+
+    #[test]
+    fn corpus_trains_model() {
+        let corpus = Corpus::new(vec![
+            sotu_document("Abraham Lincoln", 1862, "Annual Message to Congress", &["We cannot escape history. We will win."]),
+            sotu_document("Joseph R. Biden, Jr.", 2022, "Address Before a Joint Session of the Congress on the State of the Union", &["We will win."])
+        ]);
+
+        let mut bmodel = NGramModel::new(2);
+        corpus.filter(&CorpusFilter::new().with_president("Biden")).train(&mut bmodel);
+        assert_eq!(bmodel.ngram_counts.len(), 4);
+
+        let mut bmodel = NGramModel::new(2);
+        corpus.train(&mut bmodel);
+        assert_eq!(*bmodel.ngram_counts.get(&vec!["We".to_string(), "will".to_string()]).unwrap(), 2);
+
+        // Document training keeps the context across sentences
+        let mut bmodel = NGramModel::new(2);
+        corpus.train_documents(&mut bmodel);
+        assert!(bmodel.ngram_counts.contains_key(&vec!["</S>".to_string(), "We".to_string()]));
+    }
+
+    #[test]
+    fn gather_text_reports_failed_documents() {
+        let (base_url, _requested_paths) = serve_fixture_site();
+        let config = ScraperConfig {
+            base_url: base_url.to_string(),
+            collections: vec![Collection::new("broken", "/documents/broken-directory")],
+            ..ScraperConfig::default()
+        };
+
+        // One bad page doesn't stop the others being gathered
+        let sotu_scraper = SOTUScraper::gather_text_with_config(&config).unwrap();
+        assert_eq!(sotu_scraper.documents().len(), 1);
+        assert_eq!(sotu_scraper.documents()[0].year, Some(2022));
+        let expected = vec![
+            ScrapeError::HttpStatus { url: format!("{}/documents/page-that-does-not-exist", base_url), status: 404 },
+            ScrapeError::MissingContent { url: format!("{}/documents/page-without-content", base_url) }
+        ];
+        assert_eq!(sotu_scraper.failures(), expected.as_slice());
+
+        // Loaded as a corpus source the failures are an error, which still holds the documents
+        match config.load() {
+            Err(CorpusError::Incomplete { corpus, failures }) => {
+                assert_eq!(corpus.documents(), sotu_scraper.documents());
+                assert_eq!(failures, expected);
+            }
+            other => panic!("Expected an incomplete corpus, got {:?}", other)
+        }
+    }
+
+    #[test]
+    fn gather_text_fails_without_directory() {
+        let (base_url, requested_paths) = serve_fixture_site();
+
+        let config = ScraperConfig {
+            base_url: base_url.to_string(),
+            collections: vec![Collection::new("missing", "/documents/no-such-directory")],
+            ..ScraperConfig::default()
+        };
+        let got = SOTUScraper::gather_text_with_config(&config).err();
+        assert_eq!(got, Some(ScrapeError::HttpStatus { url: format!("{}/documents/no-such-directory", base_url), status: 404 }));
+
+        // Selectors are checked before anything is downloaded
+        let config = ScraperConfig {
+            base_url: base_url.to_string(),
+            content_selector: "div[".to_string(),
+            ..ScraperConfig::default()
+        };
+        let got = SOTUScraper::gather_text_with_config(&config).err();
+        assert_eq!(got, Some(ScrapeError::InvalidSelector("div[".to_string())));
+
+        let config = ScraperConfig {
+            base_url,
+            max_concurrent_downloads: 0,
+            ..ScraperConfig::default()
+        };
+        let got = SOTUScraper::gather_text_with_config(&config).err();
+        assert_eq!(got, Some(ScrapeError::InvalidConfig("max_concurrent_downloads must be at least 1".to_string())));
+        assert_eq!(requested_paths.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn gather_text_retries_transient_failures() {
+        let directory_page = r#"<table><tr><td><a href="/documents/address-1">2022</a></td><td><a href="/documents/address-2">2023</a></td></tr></table>"#;
+        let address_page = r#"<div class="field-docs-content"><p>We will win.</p></div>"#;
+        let ok = |body: &str| format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", body.len(), body);
+        let unavailable = "HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string();
+        let (base_url, server) = serve_responses(vec![
+            ok(directory_page),
+            // address-1 succeeds on its second attempt
+            unavailable.to_string(),
+            ok(address_page),
+            // address-2 never does
+            unavailable.to_string(),
+            unavailable.to_string(),
+            unavailable.to_string()
+        ]);
+
+        let config = ScraperConfig {
+            base_url: base_url.to_string(),
+            collections: vec![Collection::new("addresses", "/documents/directory")],
+            max_concurrent_downloads: 1,
+            min_request_interval: std::time::Duration::ZERO,
+            max_retries: 2,
+            initial_backoff: std::time::Duration::from_millis(10),
+            ..ScraperConfig::default()
+        };
+        let sotu_scraper = SOTUScraper::gather_text_with_config(&config).unwrap();
+        assert_eq!(sotu_scraper.documents().len(), 1);
+        assert_eq!(sotu_scraper.documents()[0].url, format!("{}/documents/address-1", base_url));
+        let expected = vec![ScrapeError::HttpStatus { url: format!("{}/documents/address-2", base_url), status: 503 }];
+        assert_eq!(sotu_scraper.failures(), expected.as_slice());
+
+        let requests = server.join().unwrap();
+        assert_eq!(requests.len(), 6);
+        assert!(requests.iter().all(|request| request.to_lowercase().contains("user-agent: grams/")));
+    }
+
+    #[test]
+    fn gather_text_resumes_from_progress_log() {
+        let (base_url, requested_paths) = serve_fixture_site();
+        let progress_log = std::env::temp_dir().join("grams_gather_text_resumes_from_progress_log.log");
+        let _ = std::fs::remove_file(&progress_log);
+
+        let config = ScraperConfig {
+            base_url: base_url.to_string(),
+            min_request_interval: std::time::Duration::from_millis(100),
+            progress_log: Some(progress_log.to_path_buf()),
+            ..ScraperConfig::default()
+        };
+        let started = std::time::Instant::now();
+        let first = SOTUScraper::gather_text_with_config(&config).unwrap();
+        // Four requests kept 100ms apart, even when downloading concurrently
+        assert!(started.elapsed() >= std::time::Duration::from_millis(300));
+        assert_eq!(requested_paths.lock().unwrap().len(), 4);
+
+        // Simulate a scrape cut off while writing its last document
+        let log_text = std::fs::read_to_string(&progress_log).unwrap();
+        let cut_off = log_text.trim_end_matches("end\n").rfind("end\n").unwrap() + "end\n".len();
+        std::fs::write(&progress_log, &log_text[..cut_off + 20]).unwrap();
+
+        // Only the directory and the unfinished document are downloaded again
+        let second = SOTUScraper::gather_text_with_config(&config).unwrap();
+        assert_eq!(requested_paths.lock().unwrap().len(), 6);
+        assert_eq!(first.documents(), second.documents());
+    }
+
+    #[test]
+    fn gather_text_resumes_from_progress_log_cut_off_mid_line() {
+        let (base_url, requested_paths) = serve_fixture_site();
+        let progress_log = std::env::temp_dir().join("grams_gather_text_resumes_from_progress_log_cut_off_mid_line.log");
+        let _ = std::fs::remove_file(&progress_log);
+
+        let config = ScraperConfig {
+            base_url: base_url.to_string(),
+            max_concurrent_downloads: 1,
+            progress_log: Some(progress_log.to_path_buf()),
+            ..ScraperConfig::default()
+        };
+        let first = SOTUScraper::gather_text_with_config(&config).unwrap();
+        assert_eq!(requested_paths.lock().unwrap().len(), 4);
+
+        // Cut the log off partway through a paragraph of its last document, with no newline
+        let log_text = std::fs::read_to_string(&progress_log).unwrap();
+        let cut_off = log_text.rfind("\nparagraph ").unwrap() + "\nparagraph The st".len();
+        std::fs::write(&progress_log, &log_text[..cut_off]).unwrap();
+
+        // The unfinished document is downloaded again, and isn't merged into the one before
+        let second = SOTUScraper::gather_text_with_config(&config).unwrap();
+        assert_eq!(requested_paths.lock().unwrap().len(), 6);
+        assert_eq!(first.documents(), second.documents());
+
+        // Every document is finished now, so only the directory is downloaded
+        let third = SOTUScraper::gather_text_with_config(&config).unwrap();
+        assert_eq!(requested_paths.lock().unwrap().len(), 7);
+        assert_eq!(first.documents(), third.documents());
+        assert_eq!(std::fs::read_to_string(&progress_log).unwrap(), log_text);
+    }
+
+    #[test]
+    fn text_file_source() {
+        let corpus = TextFileSource::new("text_samples/biden_sotu_2022.txt").load().unwrap();
+        assert_eq!(corpus.len(), 1);
+
+        let document = &corpus.documents()[0];
+        assert_eq!(document.title, "biden_sotu_2022".to_string());
+        assert_eq!(document.year, Some(2022));
+        assert!(!document.paragraphs.iter().any(|paragraph| paragraph.is_empty()));
+
+        // Trains the same way as any other source
+        let mut bmodel = NGramModel::new(3);
+        corpus.train(&mut bmodel);
+        assert!(!bmodel.ngram_counts.is_empty());
+
+        let got = TextFileSource::new("text_samples/missing.txt").load();
+        assert!(matches!(got, Err(CorpusError::Io(_))));
+    }
+
+    #[test]
+    fn text_directory_source() {
+        let directory = temp_cache_directory("grams_text_directory_source");
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(directory.join("lincoln_1862.txt"), "We cannot escape history.\n\nWe will be remembered.\n").unwrap();
+        std::fs::write(directory.join("biden_2022.txt"), "We will win.\n").unwrap();
+        std::fs::write(directory.join("notes.md"), "Not a speech.\n").unwrap();
+
+        let corpus = TextDirectorySource::new(&directory).load().unwrap();
+        let got: Vec<(&str, usize)> = corpus
+            .documents()
+            .iter()
+            .map(|document| (document.title.as_str(), document.paragraphs.len()))
+            .collect();
+        assert_eq!(got, vec![("biden_2022", 1), ("lincoln_1862", 2)]);
+
+        let corpus = TextDirectorySource::with_pattern(&directory, "lin?oln_*").load().unwrap();
+        assert_eq!(corpus.len(), 1);
+        assert_eq!(corpus.documents()[0].year, Some(1862));
+
+        let corpus = TextDirectorySource::new("text_samples").load().unwrap();
+        assert_eq!(corpus.len(), 2);
+    }
+
+    #[test]
+    fn text_directory_source_path_globs() {
+        let directory = temp_cache_directory("grams_text_directory_source_path_globs");
+        std::fs::create_dir_all(directory.join("lincoln/1862")).unwrap();
+        std::fs::create_dir_all(directory.join("biden")).unwrap();
+        std::fs::write(directory.join("washington_1790.txt"), "Fellow-Citizens.\n").unwrap();
+        std::fs::write(directory.join("biden/biden_2022.txt"), "We will win.\n").unwrap();
+        std::fs::write(directory.join("biden/notes.md"), "Not a speech.\n").unwrap();
+        std::fs::write(directory.join("lincoln/1862/lincoln_1862.txt"), "We cannot escape history.\n").unwrap();
+
+        let titles = |pattern: &str| -> Vec<String> {
+            TextDirectorySource::with_pattern(&directory, pattern)
+                .load()
+                .unwrap()
+                .documents()
+                .iter()
+                .map(|document| document.title.clone())
+                .collect()
+        };
+        // Plain file name patterns don't reach into subdirectories
+        assert_eq!(titles("*.txt"), vec!["washington_1790"]);
+        assert_eq!(titles("*/*.txt"), vec!["biden_2022"]);
+        assert_eq!(titles("**/*.txt"), vec!["biden_2022", "lincoln_1862", "washington_1790"]);
+        assert_eq!(titles("lincoln/**/*.txt"), vec!["lincoln_1862"]);
+        assert_eq!(titles("**/b*"), vec!["biden_2022"]);
+        assert_eq!(titles("biden/**"), vec!["biden_2022", "notes"]);
+    }
+
+    #[test]
+    fn corpus_jsonl_round_trip() {
+        let mut unusual = sotu_document("George Washington", 1790, "First Annual Address to Congress", &[
+            "Fellow-Citizens of the Senate and House of Representatives:",
+            "Quotes \"like this\", a backslash \\ and a\nline break — and unicode ✓."
+        ]);
+        unusual.year = None;
+        let corpus = Corpus::new(vec![
+            sotu_document("Joseph R. Biden, Jr.", 2022, "Address Before a Joint Session of the Congress on the State of the Union", &["We will win.", "Together."]),
+            unusual
+        ]);
+
+        let path = std::env::temp_dir().join("grams_corpus_jsonl_round_trip.jsonl");
+        corpus.write_jsonl(&path).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 2);
+
+        let got = JsonlSource::new(&path).load().unwrap();
+        assert_eq!(got, corpus);
+
+        // Exports from before documents were tagged with their collection still load
+        let line = r#"{"president":"Nobody","date":"","year":null,"title":"","url":"","paragraphs":["We will win."]}"#;
+        std::fs::write(&path, format!("{}\n", line)).unwrap();
+        let got = JsonlSource::new(&path).load().unwrap();
+        assert_eq!(got.documents()[0].collection, "".to_string());
+
+        std::fs::write(&path, "{\"president\": \"Nobody\"}\n").unwrap();
+        let got = JsonlSource::new(&path).load();
+        assert!(matches!(got, Err(CorpusError::Parse { line: 1, .. })));
+    }
+
+    #[test]
+    fn corpus_text_archive_round_trip() {
+        let corpus = TextDirectorySource::new("text_samples").load().unwrap();
+        let mut documents = corpus.documents().to_vec();
+        let mut document = sotu_document("Joseph R. Biden, Jr.", 2022, "Address", &["We will win.", "Together."]);
+        document.categories = vec!["Spoken Addresses and Remarks".to_string()];
+        documents.push(document);
+        let corpus = Corpus::new(documents);
+
+        let directory = temp_cache_directory("grams_corpus_text_archive_round_trip");
+        corpus.write_text_archive(&directory).unwrap();
+        assert!(directory.join("0003_2022_joseph-r-biden-jr.txt").is_file());
+
+        let got = TextArchiveSource::new(&directory).load().unwrap();
+        assert_eq!(got, corpus);
+
+        // The text files alone are a corpus too, just without the metadata
+        let got = TextDirectorySource::new(&directory).load().unwrap();
+        assert_eq!(got.len(), 3);
+        assert_eq!(got.documents()[2].paragraphs, corpus.documents()[2].paragraphs);
+
+        // Line breaks inside a paragraph are kept
+        let corpus = Corpus::new(vec![sotu_document("George Washington", 1790, "Address", &["Fellow-Citizens of the Senate\nand House of Representatives:", "In meeting you."])]);
+        corpus.write_text_archive(&directory).unwrap();
+        let got = TextArchiveSource::new(&directory).load().unwrap();
+        assert_eq!(got, corpus);
+
+        // Archives from before that, a paragraph per line, still load
+        std::fs::write(directory.join("0001_1790_george-washington.txt"), "Fellow-Citizens of the Senate\nIn meeting you.\n").unwrap();
+        let got = TextArchiveSource::new(&directory).load().unwrap();
+        assert_eq!(got.documents()[0].paragraphs, vec!["Fellow-Citizens of the Senate".to_string(), "In meeting you.".to_string()]);
+    }
+
+    #[test]
+    fn gather_collections_from_local_mirror() {
+        let (base_url, requested_paths) = serve_fixture_site();
+        let config = ScraperConfig {
+            base_url: base_url.to_string(),
+            collections: vec![Collection::state_of_the_union(), Collection::inaugural_addresses()],
+            ..ScraperConfig::default()
+        };
+
+        let sotu_scraper = SOTUScraper::gather_text_with_config(&config).unwrap();
+        let got: Vec<(&str, Option<i32>)> = sotu_scraper
+            .documents()
+            .iter()
+            .map(|document| (document.collection.as_str(), document.year))
+            .collect();
+        let expected = vec![
+            ("state-of-the-union", Some(2020)),
+            ("state-of-the-union", Some(2022)),
+            ("state-of-the-union", Some(2023)),
+            ("inaugural", Some(2021))
+        ];
+        assert_eq!(got, expected);
+        // Both directories, and every document once
+        assert_eq!(requested_paths.lock().unwrap().len(), 6);
+
+        let inaugurals = sotu_scraper.corpus().filter(&CorpusFilter::new().with_collection("inaugural"));
+        assert_eq!(inaugurals.len(), 1);
+        assert_eq!(inaugurals.documents()[0].title, "Inaugural Address".to_string());
+        assert_eq!(inaugurals.documents()[0].lines(), vec!["This is America's day", "This is democracy's day"]);
+    }
+
+    #[test]
+    fn text_extractor_drops_annotations_and_footnotes() {
+        let html = std::fs::read_to_string("tests/fixtures/ucsb/address-2010.html").unwrap();
+        let url = "https://www.presidency.ucsb.edu/documents/2010";
+        let got = SotuDocument::from_html(url, &html, &ScraperConfig::default()).unwrap();
+        let expected = vec![
+            "Madam Speaker, Vice President Biden, Members of Congress, distinguished guests, and fellow Americans:".to_string(),
+            "Our Constitution declares that from time to time, the President shall give to Congress information about the state of our Union.".to_string(),
+            "Now, I am not interested in re-litigating the past. I know that I campaigned on the promise of change.".to_string()
+        ];
+        assert_eq!(got.paragraphs, expected);
+
+        let config = ScraperConfig {
+            text_extractor: TextExtractor { annotations: Annotations::Tag, ..TextExtractor::default() },
+            ..ScraperConfig::default()
+        };
+        let got = SotuDocument::from_html(url, &html, &config).unwrap();
+        assert_eq!(got.paragraphs[1], "Our Constitution declares that from time to time, the President shall give to Congress information about the state of our Union. <applause>".to_string());
+        assert_eq!(got.paragraphs[2], "<applause>".to_string());
+        assert_eq!(got.paragraphs[3], "Now, I am not interested in re-litigating the past. <laughter> I know that I campaigned on the promise of change.".to_string());
+
+        let config = ScraperConfig {
+            text_extractor: TextExtractor { annotations: Annotations::Keep, keep_footnotes: true, ..TextExtractor::default() },
+            ..ScraperConfig::default()
+        };
+        let got = SotuDocument::from_html(url, &html, &config).unwrap();
+        assert_eq!(got.paragraphs.len(), 5);
+        assert_eq!(got.paragraphs[2], "[Applause, the Members rising]".to_string());
+        assert_eq!(got.paragraphs[4], "NOTE: The President spoke at 9:10 p.m. in the House Chamber at the U.S. Capitol.".to_string());
+    }
+
+    #[test]
+    fn text_extractor_keeps_brackets_that_are_not_stage_directions() {
+        let html = r#"<div class="field-docs-content">
+<p>They said it "can't [be] done" [sic]. [Applause] It was done. [Cheers and applause]</p>
+</div>"#;
+        let url = "https://www.presidency.ucsb.edu/documents/brackets";
+        let got = SotuDocument::from_html(url, html, &ScraperConfig::default()).unwrap();
+        assert_eq!(got.paragraphs, vec![r#"They said it "can't [be] done" [sic]. It was done."#.to_string()]);
+
+        let config = ScraperConfig {
+            text_extractor: TextExtractor { annotations: Annotations::Tag, ..TextExtractor::default() },
+            ..ScraperConfig::default()
+        };
+        let got = SotuDocument::from_html(url, html, &config).unwrap();
+        assert_eq!(got.paragraphs, vec![r#"They said it "can't [be] done" [sic]. <applause> It was done. <cheers>"#.to_string()]);
+
+        // A list of its own replaces the built-in stage directions
+        let config = ScraperConfig {
+            text_extractor: TextExtractor { stage_directions: vec!["sic".to_string()], ..TextExtractor::default() },
+            ..ScraperConfig::default()
+        };
+        let got = SotuDocument::from_html(url, html, &config).unwrap();
+        assert_eq!(got.paragraphs, vec![r#"They said it "can't [be] done" . [Applause] It was done. [Cheers and applause]"#.to_string()]);
+    }
+
+    #[test]
+    fn text_extractor_keeps_line_breaks() {
+        let html = std::fs::read_to_string("tests/fixtures/ucsb/message-1790.html").unwrap();
+        let url = "https://www.presidency.ucsb.edu/documents/1790";
+        let got = SotuDocument::from_html(url, &html, &ScraperConfig::default()).unwrap();
+        // One <br> breaks a line, two end the paragraph
+        let expected = vec![
+            "Fellow-Citizens of the Senate\nand House of Representatives:".to_string(),
+            "In meeting you I embrace with great satisfaction the opportunity.".to_string(),
+            "To be prepared for war is one of the most effectual means of preserving peace.".to_string()
+        ];
+        assert_eq!(got.paragraphs, expected);
+        assert_eq!(got.title, "First Annual Address to Congress".to_string());
+
+        let config = ScraperConfig {
+            text_extractor: TextExtractor { keep_footnotes: true, keep_headings: true, ..TextExtractor::default() },
+            ..ScraperConfig::default()
+        };
+        let got = SotuDocument::from_html(url, &html, &config).unwrap();
+        let expected = vec![
+            "Fellow-Citizens of the Senate\nand House of Representatives:".to_string(),
+            "In meeting you I embrace with great satisfaction the opportunity.1".to_string(),
+            "Of the Common Defence".to_string(),
+            "To be prepared for war is one of the most effectual means of preserving peace.".to_string(),
+            "1 The address was delivered in the Senate Chamber in Federal Hall.".to_string()
+        ];
+        assert_eq!(got.paragraphs, expected);
+    }
 }